@@ -0,0 +1,108 @@
+//! Checking every bullet against every target against the broad phase of
+//! the game, rebuilding and querying a `SpatialHash` each frame, with a mix
+//! of box and circle colliders. Run with `cargo bench --bench collision`.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// The game is a binary, pull the grid and the shapes in directly
+#[allow(dead_code)]
+#[path = "../src/spatial_hash.rs"]
+mod spatial_hash;
+
+#[allow(dead_code)]
+#[path = "../src/collision/collider.rs"]
+mod collider;
+
+use collider::{Collider, COLLIDER_SIZE};
+use spatial_hash::SpatialHash;
+
+const TARGETS: usize = 50;
+const FRAMES: u32 = 100;
+/// Roughly the visible area
+const AREA: Vec2 = Vec2::new(460., 260.);
+
+/// Every other collider is a circle, some of them offset like boss parts
+fn colliders(rng: &mut StdRng, count: usize) -> Vec<(Entity, Vec2, Collider)> {
+    return (0..count)
+        .map(|index| {
+            let position = Vec2::new(rng.gen_range(0.0..AREA.x), rng.gen_range(0.0..AREA.y));
+            let collider = match index % 4 {
+                0 => Collider::default(),
+                1 => Collider::circle(rng.gen_range(2.0..8.0)),
+                2 => Collider::aabb(COLLIDER_SIZE * rng.gen_range(0.5_f32..2.0)),
+                _ => Collider::circle(rng.gen_range(4.0..16.0)).with_offset(Vec2::new(0., 6.)),
+            };
+            (Entity::from_raw(index as u32), position, collider)
+        })
+        .collect();
+}
+
+fn naive(targets: &[(Entity, Vec2, Collider)], bullets: &[(Entity, Vec2, Collider)]) -> usize {
+    let mut hits = 0;
+    for (_, target, target_collider) in targets {
+        for (_, bullet, bullet_collider) in bullets {
+            if target_collider.overlaps(*target, bullet_collider, *bullet) {
+                hits += 1;
+            }
+        }
+    }
+    return hits;
+}
+
+fn grid(
+    grid: &mut SpatialHash,
+    targets: &[(Entity, Vec2, Collider)],
+    bullets: &[(Entity, Vec2, Collider)],
+) -> usize {
+    grid.clear();
+    for (entity, bullet, collider) in bullets {
+        collider.insert_into(grid, *entity, *bullet);
+    }
+
+    let mut hits = 0;
+    for (_, target, target_collider) in targets {
+        for other in target_collider.nearby(grid, *target) {
+            let (_, bullet, bullet_collider) = &bullets[other.id() as usize];
+            if target_collider.overlaps(*target, bullet_collider, *bullet) {
+                hits += 1;
+            }
+        }
+    }
+    return hits;
+}
+
+fn time(mut frame: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut hits = 0;
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        hits = frame();
+    }
+    return (start.elapsed() / FRAMES, hits);
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let targets = colliders(&mut rng, TARGETS);
+    let mut spatial_hash = SpatialHash::new(32.);
+
+    for bullet_count in [1000, 5000, 10000] {
+        let bullets = colliders(&mut rng, bullet_count);
+
+        let (naive_time, naive_hits) = time(|| naive(&targets, &bullets));
+        let (grid_time, grid_hits) = time(|| grid(&mut spatial_hash, &targets, &bullets));
+        // The broad phase must not lose any pair
+        assert_eq!(grid_hits, naive_hits);
+
+        println!(
+            "{} bullets, {} targets: naive {:?}, grid {:?} per frame ({:.1}x)",
+            bullet_count,
+            TARGETS,
+            naive_time,
+            grid_time,
+            naive_time.as_secs_f64() / grid_time.as_secs_f64()
+        );
+    }
+}
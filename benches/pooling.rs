@@ -0,0 +1,103 @@
+//! Spawning and despawning projectiles every frame against reusing them from
+//! an `EntityPool`, at bullet hell rates. Run with `cargo bench --bench pooling`.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+// The game is a binary, pull the pool in directly
+#[allow(dead_code)]
+#[path = "../src/pool/entity_pool.rs"]
+mod entity_pool;
+
+use entity_pool::EntityPool;
+
+const SHOTS_PER_FRAME: usize = 500;
+/// Frames a projectile lives, so about 15000 are alive at once
+const LIFETIME: u32 = 30;
+const FRAMES: u32 = 600;
+
+#[derive(Component)]
+struct Bullet {
+    age: u32,
+}
+
+#[derive(Component)]
+struct Velocity(Vec2);
+
+fn spawn_and_despawn(mut commands: Commands, mut bullet_query: Query<(Entity, &mut Bullet)>) {
+    for (entity, mut bullet) in bullet_query.iter_mut() {
+        bullet.age += 1;
+        if bullet.age >= LIFETIME {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for _ in 0..SHOTS_PER_FRAME {
+        commands
+            .spawn_bundle(SpriteSheetBundle::default())
+            .insert(Bullet { age: 0 })
+            .insert(Velocity(Vec2::Y));
+    }
+}
+
+fn acquire_and_release(
+    mut commands: Commands,
+    mut pool: ResMut<EntityPool>,
+    mut bullet_query: Query<(Entity, &mut Bullet)>,
+) {
+    for (entity, mut bullet) in bullet_query.iter_mut() {
+        bullet.age += 1;
+        if bullet.age >= LIFETIME {
+            pool.release::<(Bullet, Velocity)>(&mut commands, entity);
+        }
+    }
+
+    for _ in 0..SHOTS_PER_FRAME {
+        let entity = pool.acquire(&mut commands);
+        commands
+            .entity(entity)
+            .insert_bundle(SpriteSheetBundle::default())
+            .insert(Bullet { age: 0 })
+            .insert(Velocity(Vec2::Y));
+    }
+}
+
+fn recycle(mut pool: ResMut<EntityPool>) {
+    pool.recycle();
+}
+
+/// Average time of a frame once the number of projectiles is steady
+fn run(name: &str, app: &mut App) -> Duration {
+    for _ in 0..LIFETIME * 2 {
+        app.update();
+    }
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        app.update();
+    }
+    let frame_time = start.elapsed() / FRAMES;
+
+    println!("{}: {:?} per frame", name, frame_time);
+    return frame_time;
+}
+
+fn main() {
+    let mut spawning = App::new();
+    spawning.add_system(spawn_and_despawn);
+    let spawning_time = run("spawn and despawn", &mut spawning);
+
+    let mut pooling = App::new();
+    pooling
+        .init_resource::<EntityPool>()
+        .add_system(acquire_and_release)
+        .add_system_to_stage(CoreStage::Last, recycle);
+    let pooling_time = run("pooled", &mut pooling);
+
+    println!(
+        "pooled frames take {:.0}% of the time",
+        pooling_time.as_secs_f64() / spawning_time.as_secs_f64() * 100.
+    );
+    println!("{:?}", pooling.world.resource::<EntityPool>().stats());
+}
@@ -1,7 +1,11 @@
 use bevy::prelude::*;
 use iyes_loopless::prelude::ConditionSet;
 
-use crate::{player::Player, GameState};
+use crate::{
+    player::Player,
+    pool::{despawn_or_release, EntityPools, Pooled},
+    GameState,
+};
 
 pub struct AnimatorPlugin;
 
@@ -38,14 +42,17 @@ pub fn spawn_animated_entity_with_color(
     is_looping: bool,
     color: Color,
 ) -> Entity {
-    return build_animated_entity(
+    let entity = commands.spawn().id();
+    insert_animated_bundle(
         commands,
+        entity,
         translation,
         animation_sheet,
         frame_duration,
         is_looping,
         color,
     );
+    return entity;
 }
 pub fn spawn_animated_entity(
     commands: &mut Commands,
@@ -54,29 +61,36 @@ pub fn spawn_animated_entity(
     frame_duration: f32,
     is_looping: bool,
 ) -> Entity {
-    return build_animated_entity(
+    let entity = commands.spawn().id();
+    insert_animated_bundle(
         commands,
+        entity,
         translation,
         animation_sheet,
         frame_duration,
         is_looping,
         Color::WHITE,
     );
+    return entity;
 }
 
-fn build_animated_entity(
+/// Turns `entity` into an animated sprite, restarting the animation of a
+/// reused entity
+pub fn insert_animated_bundle(
     commands: &mut Commands,
+    entity: Entity,
     translation: Vec3,
     animation_sheet: &AnimationSheet,
     frame_duration: f32,
     is_looping: bool,
     color: Color,
-) -> Entity {
+) {
     let mut animated_sprite = TextureAtlasSprite::new(animation_sheet.frames[0]);
     animated_sprite.color = color;
 
-    return commands
-        .spawn_bundle(SpriteSheetBundle {
+    commands
+        .entity(entity)
+        .insert_bundle(SpriteSheetBundle {
             sprite: animated_sprite,
             texture_atlas: animation_sheet.handle.clone(),
             transform: Transform {
@@ -90,19 +104,24 @@ fn build_animated_entity(
             frames: animation_sheet.frames.clone(),
             current_frame: 0,
             is_looping: is_looping,
-        })
-        .id();
+        });
 }
 
 fn loop_animated_frames(
     mut commands: Commands,
     mut sprites_query: Query<
-        (Entity, &mut TextureAtlasSprite, &mut FrameAnimation),
+        (
+            Entity,
+            &mut TextureAtlasSprite,
+            &mut FrameAnimation,
+            Option<&Pooled>,
+        ),
         Without<Player>,
     >,
+    mut pools: ResMut<EntityPools>,
     time: Res<Time>,
 ) {
-    for (entity, mut sprite, mut animation) in sprites_query.iter_mut() {
+    for (entity, mut sprite, mut animation, pooled) in sprites_query.iter_mut() {
         animation.timer.tick(time.delta());
         if animation.timer.just_finished() {
             animation.current_frame = (animation.current_frame + 1) % animation.frames.len();
@@ -110,7 +129,7 @@ fn loop_animated_frames(
 
             if !animation.is_looping && sprite.index == animation.frames[animation.frames.len() - 1]
             {
-                commands.entity(entity).despawn();
+                despawn_or_release(&mut commands, &mut pools, entity, pooled);
             }
 
             animation.timer.reset();
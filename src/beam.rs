@@ -0,0 +1,251 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{ecs::system::Command, prelude::*, sprite::Anchor, time::Stopwatch};
+use iyes_loopless::prelude::ConditionSet;
+
+use crate::{
+    animation::{insert_animated_bundle, AnimationSheet},
+    boss::BossPart,
+    collision::{ray_cast, Collider, CollisionLayer, CollisionLayers},
+    enemy::Enemy,
+    event_system::DamageEvent,
+    pool::{EntityPools, PoolKind},
+    shoot::Shootable,
+    weapon::{ProjectileSheet, WeaponsAsset},
+    CoreAssets, GameState,
+};
+
+/// Seconds between two hit effects at the end of a beam
+const HIT_EFFECT_INTERVAL: f32 = 0.1;
+
+/// Beam of a `Shootable` whose weapon is a beam, added while it fires
+#[derive(Component)]
+pub struct BeamState {
+    visual: Entity,
+    /// Damage not yet sent, `DamageEvent` only takes whole points
+    damage_carry: f32,
+    hit_effect_time: Stopwatch,
+}
+
+/// Drawn in world space rather than as a child, so it does not pick up the
+/// shooter's rotation and scale
+#[derive(Component)]
+struct BeamVisual {
+    shooter: Entity,
+}
+
+/// Adds the `BeamState` to a shooter that may have been despawned earlier in
+/// the frame, the beam visual goes with it in that case
+struct InsertBeamState {
+    shooter: Entity,
+    state: BeamState,
+}
+
+impl Command for InsertBeamState {
+    fn write(self, world: &mut World) {
+        match world.get_entity_mut(self.shooter) {
+            Some(mut shooter) => {
+                shooter.insert(self.state);
+            }
+            None => {
+                world.despawn(self.state.visual);
+            }
+        }
+    }
+}
+
+pub struct BeamPlugin;
+
+impl Plugin for BeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::InGame)
+                .with_system(fire_beams)
+                .with_system(despawn_orphaned_beams)
+                .into(),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fire_beams(
+    mut commands: Commands,
+    mut shooter_query: Query<(
+        Entity,
+        &GlobalTransform,
+        &mut Shootable,
+        Option<&Enemy>,
+        Option<&mut BeamState>,
+    )>,
+    collider_query: Query<(
+        Entity,
+        &GlobalTransform,
+        &Collider,
+        &CollisionLayers,
+        Option<&BossPart>,
+    )>,
+    mut visual_query: Query<(&mut Transform, &mut TextureAtlasSprite), With<BeamVisual>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut pools: ResMut<EntityPools>,
+    core_asssets: Res<CoreAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
+    time: Res<Time>,
+) {
+    let weapons = match weapons_assets.get(&core_asssets.weapons) {
+        Some(weapons) => weapons,
+        None => return,
+    };
+
+    for (shooter_entity, shooter_transform, mut shootable, enemy, beam_state) in
+        shooter_query.iter_mut()
+    {
+        let weapon = weapons.weapons.get(&shootable.weapon);
+        let beam = weapon.and_then(|weapon| weapon.beam);
+        let (weapon, beam) = match (weapon, beam) {
+            (Some(weapon), Some(beam)) if shootable.is_shooting && !shootable.gauge.locked => {
+                (weapon, beam)
+            }
+            _ => {
+                // Not firing a beam (anymore)
+                if let Some(beam_state) = beam_state {
+                    commands.entity(beam_state.visual).despawn();
+                    commands.entity(shooter_entity).remove::<BeamState>();
+                }
+                continue;
+            }
+        };
+
+        if let Some(resource) = &weapon.resource {
+            shootable.gauge.spend(resource, time.delta_seconds());
+        }
+
+        let origin = shooter_transform.translation().truncate();
+        let direction = shootable
+            .direction
+            .truncate()
+            .try_normalize()
+            .unwrap_or(Vec2::Y);
+
+        // Beams hit whatever a bullet of the same side would
+        let beam_layers = CollisionLayers::new(if enemy.is_some() {
+            CollisionLayer::EnemyBullet
+        } else {
+            CollisionLayer::PlayerBullet
+        });
+        let hit = ray_cast(
+            origin,
+            direction,
+            beam.length,
+            beam.width / 2.,
+            collider_query
+                .iter()
+                .filter(|(_, _, _, layers, _)| beam_layers.interacts(layers))
+                .map(|(entity, transform, collider, ..)| {
+                    (entity, transform.translation().truncate(), collider)
+                }),
+        );
+        let length = hit.as_ref().map_or(beam.length, |hit| hit.distance);
+
+        let mut beam_state = match beam_state {
+            Some(beam_state) => beam_state,
+            None => {
+                let sheet = match weapon.sprite.sheet {
+                    ProjectileSheet::General => &core_asssets.general,
+                    ProjectileSheet::Missile => &core_asssets.missile,
+                };
+                let mut sprite = TextureAtlasSprite::new(weapon.sprite.frames[0]);
+                sprite.anchor = Anchor::BottomCenter;
+
+                let visual = commands
+                    .spawn_bundle(SpriteSheetBundle {
+                        sprite,
+                        texture_atlas: sheet.clone(),
+                        ..default()
+                    })
+                    .insert(Name::new("Beam"))
+                    .insert(BeamVisual {
+                        shooter: shooter_entity,
+                    })
+                    .id();
+                commands.add(InsertBeamState {
+                    shooter: shooter_entity,
+                    state: BeamState {
+                        visual,
+                        damage_carry: 0.,
+                        hit_effect_time: Stopwatch::new(),
+                    },
+                });
+                // Drawn and applied from the next frame on
+                continue;
+            }
+        };
+
+        if let Ok((mut visual_transform, mut sprite)) = visual_query.get_mut(beam_state.visual) {
+            visual_transform.translation = origin.extend(99.);
+            visual_transform.rotation =
+                Quat::from_rotation_z(direction.y.atan2(direction.x) - FRAC_PI_2);
+            sprite.custom_size = Some(Vec2::new(beam.width, length));
+        }
+
+        let hit = match hit {
+            Some(hit) => hit,
+            None => {
+                beam_state.damage_carry = 0.;
+                continue;
+            }
+        };
+
+        beam_state.damage_carry += beam.damage_per_second * time.delta_seconds();
+        let damage = beam_state.damage_carry.floor();
+        if damage >= 1. {
+            beam_state.damage_carry -= damage;
+
+            // Terrain only stops the beam, boss parts share the health of the
+            // boss
+            let target = match collider_query.get(hit.entity) {
+                Ok((_, _, _, layers, _)) if layers.layer == CollisionLayer::Terrain => None,
+                Ok((_, _, _, _, Some(boss_part))) => Some(boss_part.boss),
+                _ => Some(hit.entity),
+            };
+            if let Some(target) = target {
+                damage_events.send(DamageEvent {
+                    damage: damage as i32,
+                    target,
+                    translation: hit.point.extend(100.),
+                });
+            }
+        }
+
+        beam_state.hit_effect_time.tick(time.delta());
+        if beam_state.hit_effect_time.elapsed_secs() >= HIT_EFFECT_INTERVAL {
+            beam_state.hit_effect_time.reset();
+            let hit_effect = pools.acquire(&mut commands, PoolKind::Effect);
+            insert_animated_bundle(
+                &mut commands,
+                hit_effect,
+                hit.point.extend(101.),
+                &AnimationSheet {
+                    handle: core_asssets.general.clone(),
+                    frames: vec![4, 8, 9],
+                },
+                0.03,
+                false,
+                Color::WHITE,
+            );
+        }
+    }
+}
+
+/// Beams of shooters that were despawned while firing
+fn despawn_orphaned_beams(
+    mut commands: Commands,
+    visual_query: Query<(Entity, &BeamVisual)>,
+    shooter_query: Query<(), With<BeamState>>,
+) {
+    for (visual, beam_visual) in visual_query.iter() {
+        if shooter_query.get(beam_visual.shooter).is_err() {
+            commands.entity(visual).despawn();
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use bevy::{prelude::*, time::Stopwatch};
+use bevy_pixel_camera::PixelProjection;
+use iyes_loopless::prelude::ConditionSet;
+use serde::Deserialize;
+
+use crate::{
+    collision::{Collider, CollisionLayer, CollisionLayers, COLLIDER_SIZE},
+    components::Health,
+    enemy::Enemy,
+    entities::entity_loader::craete_entity_from_atlas,
+    event_system::{EnemyDestroyed, StageClearEvent},
+    shoot::Shootable,
+    spawners::wave_trigger::ScriptFlags,
+    CoreAssets, GameState,
+};
+
+/// Flag raised in `ScriptFlags` once the boss has been destroyed
+pub const BOSS_DEFEATED_FLAG: &str = "boss_defeated";
+
+// Data =========================================================
+#[derive(Deserialize, Clone)]
+pub struct BossDefinition {
+    pub health: i32,
+    /// Seconds the warning is shown before the boss flies in
+    #[serde(default = "default_warning_duration")]
+    pub warning_duration: f32,
+    /// Position relative to the camera
+    pub position: [f32; 2],
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    pub parts: Vec<BossPartDefinition>,
+    pub phases: Vec<BossPhaseDefinition>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BossPartDefinition {
+    pub offset: [f32; 2],
+    #[serde(default)]
+    pub sprite: usize,
+    /// Defaults to a box the size of the sprite
+    #[serde(default)]
+    pub hitbox: Option<HitboxDefinition>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HitboxDefinition {
+    Aabb {
+        size: [f32; 2],
+        #[serde(default)]
+        offset: [f32; 2],
+    },
+    Circle {
+        radius: f32,
+        #[serde(default)]
+        offset: [f32; 2],
+    },
+    /// Hit when any of `parts` is, their offsets are relative to this one
+    Compound {
+        parts: Vec<HitboxDefinition>,
+        #[serde(default)]
+        offset: [f32; 2],
+    },
+}
+
+impl HitboxDefinition {
+    /// Collider of a part drawn `scale` times its sprite size
+    fn collider(&self, scale: f32) -> Collider {
+        return match self {
+            HitboxDefinition::Aabb { size, offset } => {
+                Collider::aabb(Vec2::from(*size) * scale).with_offset(Vec2::from(*offset) * scale)
+            }
+            HitboxDefinition::Circle { radius, offset } => {
+                Collider::circle(radius * scale).with_offset(Vec2::from(*offset) * scale)
+            }
+            HitboxDefinition::Compound { parts, offset } => {
+                Collider::compound(parts.iter().map(|part| part.collider(scale)).collect())
+                    .with_offset(Vec2::from(*offset) * scale)
+            }
+        };
+    }
+
+    /// `path` is where the hitbox is in the level file, for the error
+    fn validate(&self, path: &str) -> Result<(), String> {
+        match self {
+            HitboxDefinition::Aabb { size, .. } => {
+                if size.iter().any(|side| side.is_nan() || *side <= 0.) {
+                    return Err(format!("{} must have a size greater than 0", path));
+                }
+            }
+            HitboxDefinition::Circle { radius, .. } => {
+                if radius.is_nan() || *radius <= 0. {
+                    return Err(format!("{} must have a radius greater than 0", path));
+                }
+            }
+            HitboxDefinition::Compound { parts, .. } => {
+                if parts.is_empty() {
+                    return Err(format!("{} needs at least one part", path));
+                }
+                for (index, part) in parts.iter().enumerate() {
+                    part.validate(&format!("{}.parts[{}]", path, index))?;
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BossPhaseDefinition {
+    /// Phase starts once health drops to this fraction of max health
+    pub health_threshold: f32,
+    #[serde(default)]
+    pub movement: BossMovement,
+    #[serde(default)]
+    pub weapons: Vec<BossWeapon>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BossMovement {
+    #[default]
+    Hold,
+    Sway {
+        width: f32,
+        speed: f32,
+    },
+    Circle {
+        radius: f32,
+        speed: f32,
+    },
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BossWeapon {
+    /// Index into `parts`
+    pub part: usize,
+    /// Weapon id from the weapons file
+    pub weapon: String,
+    pub direction: [f32; 2],
+}
+
+fn default_warning_duration() -> f32 {
+    3.
+}
+
+fn default_scale() -> f32 {
+    1.
+}
+
+impl BossDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.health <= 0 {
+            return Err("boss.health must be greater than 0".to_string());
+        }
+        if !self.warning_duration.is_finite() || self.warning_duration < 0. {
+            return Err("boss.warning_duration must be 0 or greater".to_string());
+        }
+        if !self.scale.is_finite() || self.scale <= 0. {
+            return Err("boss.scale must be greater than 0".to_string());
+        }
+        if self.parts.is_empty() {
+            return Err("boss.parts needs at least one part".to_string());
+        }
+        if self.phases.is_empty() {
+            return Err("boss.phases needs at least one phase".to_string());
+        }
+
+        for (index, part) in self.parts.iter().enumerate() {
+            if let Some(hitbox) = &part.hitbox {
+                hitbox.validate(&format!("boss.parts[{}].hitbox", index))?;
+            }
+        }
+
+        for (index, phase) in self.phases.iter().enumerate() {
+            if !(0. ..=1.).contains(&phase.health_threshold) {
+                return Err(format!(
+                    "boss.phases[{}].health_threshold must be between 0 and 1",
+                    index
+                ));
+            }
+            // Phases are picked by the last threshold the health is under
+            if index > 0 && phase.health_threshold >= self.phases[index - 1].health_threshold {
+                return Err(format!(
+                    "boss.phases[{}].health_threshold must be lower than the previous phase's",
+                    index
+                ));
+            }
+            for weapon in phase.weapons.iter() {
+                if weapon.part >= self.parts.len() {
+                    return Err(format!(
+                        "boss.phases[{}] has a weapon on part {}, the boss has {} parts",
+                        index,
+                        weapon.part,
+                        self.parts.len()
+                    ));
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+impl BossMovement {
+    fn offset(&self, seconds: f32) -> Vec2 {
+        match self {
+            BossMovement::Hold => Vec2::ZERO,
+            BossMovement::Sway { width, speed } => Vec2::new((seconds * speed).sin() * width, 0.),
+            BossMovement::Circle { radius, speed } => {
+                Vec2::new((seconds * speed).cos(), (seconds * speed).sin()) * *radius
+            }
+        }
+    }
+}
+// =============================================================
+
+// Components ==================================================
+#[derive(Component)]
+pub struct Boss {
+    phases: Vec<BossPhaseDefinition>,
+    current_phase: Option<usize>,
+    max_health: i32,
+    parts: Vec<Entity>,
+    position: Vec2,
+    time: Stopwatch,
+}
+
+/// Collider of a boss, damage taken by a part goes to the boss
+#[derive(Component)]
+pub struct BossPart {
+    pub boss: Entity,
+}
+
+#[derive(Component)]
+struct BossWarning;
+
+#[derive(Component)]
+struct BossHealthBar;
+// =============================================================
+
+enum BossState {
+    Waiting,
+    Intro(Timer),
+    Fighting(Entity),
+    Defeated,
+}
+
+/// Boss of the current stage, inserted when the level has one
+pub struct BossEncounter {
+    definition: BossDefinition,
+    state: BossState,
+}
+
+impl BossEncounter {
+    pub fn new(definition: BossDefinition) -> Self {
+        Self {
+            definition,
+            state: BossState::Waiting,
+        }
+    }
+
+    /// Starts the intro warning, does nothing once the encounter is running
+    pub fn start(&mut self) {
+        if let BossState::Waiting = self.state {
+            self.state =
+                BossState::Intro(Timer::from_seconds(self.definition.warning_duration, false));
+        }
+    }
+
+    pub fn is_defeated(&self) -> bool {
+        return matches!(self.state, BossState::Defeated);
+    }
+}
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::InGame)
+                .run_if_resource_exists::<BossEncounter>()
+                .with_system(boss_intro)
+                .with_system(boss_phases)
+                .with_system(boss_movement)
+                .with_system(update_boss_health_bar)
+                .with_system(boss_defeated)
+                .into(),
+        );
+    }
+}
+
+fn boss_intro(
+    mut commands: Commands,
+    mut encounter: ResMut<BossEncounter>,
+    mut warning_query: Query<(Entity, &mut Visibility), With<BossWarning>>,
+    camera_query: Query<&Transform, With<PixelProjection>>,
+    core_assets: Res<CoreAssets>,
+    time: Res<Time>,
+) {
+    let encounter = &mut *encounter;
+    let timer = match &mut encounter.state {
+        BossState::Intro(timer) => timer,
+        _ => {
+            // Also catches a warning spawned in the frame the intro ended
+            for (warning_entity, _) in warning_query.iter() {
+                commands.entity(warning_entity).despawn_recursive();
+            }
+            return;
+        }
+    };
+
+    if warning_query.is_empty() {
+        commands
+            .spawn_bundle(
+                TextBundle::from_section(
+                    "WARNING",
+                    TextStyle {
+                        font: core_assets.font.clone(),
+                        font_size: 60.,
+                        color: Color::RED,
+                    },
+                )
+                .with_style(Style {
+                    align_self: AlignSelf::Center,
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Percent(35.),
+                        ..default()
+                    },
+                    ..default()
+                }),
+            )
+            .insert(BossWarning);
+    }
+
+    timer.tick(time.delta());
+    for (_, mut visibility) in warning_query.iter_mut() {
+        visibility.is_visible = (timer.elapsed_secs() * 4.) as u32 % 2 == 0;
+    }
+
+    if timer.finished() {
+        let camera_position = camera_query.single().translation.truncate();
+        let boss = spawn_boss(
+            &mut commands,
+            &core_assets,
+            &encounter.definition,
+            camera_position,
+        );
+        spawn_health_bar(&mut commands);
+
+        encounter.state = BossState::Fighting(boss);
+    }
+}
+
+fn spawn_boss(
+    commands: &mut Commands,
+    core_assets: &CoreAssets,
+    definition: &BossDefinition,
+    camera_position: Vec2,
+) -> Entity {
+    let position = Vec2::from(definition.position);
+    let boss_entity = commands
+        .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+            (camera_position + position).extend(100.),
+        )))
+        .id();
+
+    let parts: Vec<Entity> = definition
+        .parts
+        .iter()
+        .map(|part| {
+            let translation = (Vec2::from(part.offset) * definition.scale).extend(0.);
+            let part_entity =
+                craete_entity_from_atlas(commands, &core_assets.plane, part.sprite, translation);
+
+            commands
+                .entity(part_entity)
+                .insert(Name::new(format!("BossPart_{}", part_entity.id())))
+                .insert(
+                    Transform::from_translation(translation)
+                        .with_scale(Vec3::splat(definition.scale)),
+                )
+                .insert(Enemy)
+                .insert(
+                    part.hitbox
+                        .as_ref()
+                        .map_or(Collider::aabb(COLLIDER_SIZE * definition.scale), |hitbox| {
+                            hitbox.collider(definition.scale)
+                        }),
+                )
+                .insert(CollisionLayers::new(CollisionLayer::Enemy))
+                .insert(BossPart { boss: boss_entity })
+                .insert(Shootable::new(
+                    String::new(),
+                    Vec3::new(0., -1., 0.),
+                    part_entity,
+                ));
+
+            part_entity
+        })
+        .collect();
+
+    commands
+        .entity(boss_entity)
+        .insert(Name::new(format!("Boss_{}", boss_entity.id())))
+        .insert(Boss {
+            phases: definition.phases.clone(),
+            current_phase: None,
+            max_health: definition.health,
+            parts: parts.clone(),
+            position,
+            time: Stopwatch::new(),
+        })
+        .insert(Health {
+            amount: definition.health,
+        })
+        .push_children(&parts);
+
+    return boss_entity;
+}
+
+fn spawn_health_bar(commands: &mut Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(60.), Val::Px(12.)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Percent(20.),
+                    top: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            },
+            color: Color::rgb(0.15, 0.15, 0.15).into(),
+            ..default()
+        })
+        .insert(BossHealthBar)
+        .with_children(|parent| {
+            parent.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    ..default()
+                },
+                color: Color::RED.into(),
+                ..default()
+            });
+        });
+}
+
+/// Switches to the last phase whose threshold the boss health is under and
+/// arms the weapons of that phase
+fn boss_phases(
+    mut boss_query: Query<(&mut Boss, &Health)>,
+    mut shootable_query: Query<&mut Shootable, With<BossPart>>,
+) {
+    for (mut boss, health) in boss_query.iter_mut() {
+        let health_fraction = health.amount as f32 / boss.max_health as f32;
+        let phase_index = boss
+            .phases
+            .iter()
+            .rposition(|phase| health_fraction <= phase.health_threshold)
+            .unwrap_or(0);
+
+        if boss.current_phase == Some(phase_index) {
+            continue;
+        }
+        boss.current_phase = Some(phase_index);
+        boss.time.reset();
+
+        for part in boss.parts.iter() {
+            if let Ok(mut shootable) = shootable_query.get_mut(*part) {
+                shootable.is_shooting = false;
+            }
+        }
+
+        for weapon in boss.phases[phase_index].weapons.iter() {
+            if let Ok(mut shootable) = shootable_query.get_mut(boss.parts[weapon.part]) {
+                shootable.is_shooting = true;
+                shootable.weapon = weapon.weapon.clone();
+                shootable.direction = Vec2::from(weapon.direction).normalize_or_zero().extend(0.);
+                shootable.reset();
+            }
+        }
+    }
+}
+
+fn boss_movement(
+    mut boss_query: Query<(&mut Boss, &mut Transform)>,
+    camera_query: Query<&Transform, (With<PixelProjection>, Without<Boss>)>,
+    time: Res<Time>,
+) {
+    let camera_position = camera_query.single().translation.truncate();
+
+    for (mut boss, mut transform) in boss_query.iter_mut() {
+        boss.time.tick(time.delta());
+
+        let movement = match boss.current_phase {
+            Some(phase) => &boss.phases[phase].movement,
+            None => &BossMovement::Hold,
+        };
+        let offset = movement.offset(boss.time.elapsed_secs());
+
+        transform.translation = (camera_position + boss.position + offset).extend(100.);
+    }
+}
+
+fn update_boss_health_bar(
+    boss_query: Query<(&Boss, &Health), Changed<Health>>,
+    health_bar_query: Query<&Children, With<BossHealthBar>>,
+    mut style_query: Query<&mut Style>,
+) {
+    for (boss, health) in boss_query.iter() {
+        let percent = (health.amount as f32 / boss.max_health as f32).clamp(0., 1.) * 100.;
+
+        for children in health_bar_query.iter() {
+            for child in children.iter() {
+                if let Ok(mut style) = style_query.get_mut(*child) {
+                    style.size.width = Val::Percent(percent);
+                }
+            }
+        }
+    }
+}
+
+/// Boss entity is despawned by the damage events once its health runs out
+fn boss_defeated(
+    mut commands: Commands,
+    mut encounter: ResMut<BossEncounter>,
+    mut flags: ResMut<ScriptFlags>,
+    mut destroyed_events: EventReader<EnemyDestroyed>,
+    mut stage_clear_events: EventWriter<StageClearEvent>,
+    health_bar_query: Query<Entity, With<BossHealthBar>>,
+) {
+    let boss_entity = match encounter.state {
+        BossState::Fighting(entity) => entity,
+        _ => return,
+    };
+
+    if destroyed_events
+        .iter()
+        .any(|destroyed| destroyed.entity == boss_entity)
+    {
+        for health_bar in health_bar_query.iter() {
+            commands.entity(health_bar).despawn_recursive();
+        }
+
+        encounter.state = BossState::Defeated;
+        flags.set(BOSS_DEFEATED_FLAG);
+        stage_clear_events.send(StageClearEvent);
+    }
+}
@@ -0,0 +1,241 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    time::Stopwatch,
+    utils::BoxedFuture,
+};
+use iyes_loopless::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    event_system::EnemyEscaped, spawners::level_asset::LevelAsset, CoreAssets, GameState, Score,
+    UIScore,
+};
+
+// Data =========================================================
+/// Ordered list of stages, loaded from `*.campaign.toml`
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "8d3f2a61-4c9e-4b7a-a2d5-6e1f0c9b7a34"]
+pub struct CampaignAsset {
+    pub stages: Vec<StageDefinition>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct StageDefinition {
+    pub name: String,
+    pub background: String,
+    /// Level file with the waves and the boss of the stage
+    pub level: String,
+    /// Clearing the stage faster than this gives a time bonus
+    #[serde(default = "default_par_time")]
+    pub par_time: f32,
+}
+
+fn default_par_time() -> f32 {
+    120.
+}
+
+#[derive(Default)]
+pub struct CampaignAssetLoader;
+
+impl AssetLoader for CampaignAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display().to_string();
+
+            let campaign: CampaignAsset = toml::from_slice(bytes).map_err(|error| {
+                bevy::asset::Error::msg(format!("invalid campaign file `{}`: {}", path, error))
+            })?;
+            if campaign.stages.is_empty() {
+                return Err(bevy::asset::Error::msg(format!(
+                    "invalid campaign file `{}`: needs at least one stage",
+                    path
+                )));
+            }
+
+            load_context.set_default_asset(LoadedAsset::new(campaign));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["campaign.toml"]
+    }
+}
+// =============================================================
+
+/// Where the player is in the campaign, reset when a new game starts
+#[derive(Default)]
+pub struct CampaignProgress {
+    pub stage_index: usize,
+}
+
+/// Assets of the stage being played
+pub struct StageAssets {
+    pub name: String,
+    pub background: Handle<Image>,
+    pub level: Handle<LevelAsset>,
+    pub par_time: f32,
+}
+
+/// Tally of the stage being played, shown on the results screen
+#[derive(Default)]
+pub struct StageStats {
+    pub kills: u32,
+    /// Enemies that left the end of their path alive
+    pub escaped: u32,
+    pub time: Stopwatch,
+}
+
+/// Points per second the stage is cleared under its par time
+const TIME_BONUS_PER_SECOND: f32 = 50.;
+
+#[derive(Component)]
+struct StageClearUi;
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<CampaignAsset>()
+            .init_asset_loader::<CampaignAssetLoader>()
+            .init_resource::<CampaignProgress>()
+            .add_enter_system(GameState::LoadingStage, load_stage)
+            .add_system(wait_for_stage.run_in_state(GameState::LoadingStage))
+            .add_enter_system(GameState::InGame, reset_stage_stats)
+            .add_system(tick_stage_stats.run_in_state(GameState::InGame))
+            .add_enter_system(GameState::StageClear, setup_stage_clear)
+            .add_system(stage_clear_system.run_in_state(GameState::StageClear));
+    }
+}
+
+fn load_stage(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    core_assets: Res<CoreAssets>,
+    campaigns: Res<Assets<CampaignAsset>>,
+    progress: Res<CampaignProgress>,
+) {
+    let campaign = campaigns
+        .get(&core_assets.campaign)
+        .expect("campaign asset should be loaded before loading a stage");
+    // Endless mode plays on the first stage's background without its waves
+    let stage = campaign
+        .stages
+        .get(progress.stage_index)
+        .unwrap_or(&campaign.stages[0]);
+
+    println!("loading stage {}", stage.name);
+
+    commands.insert_resource(StageAssets {
+        name: stage.name.clone(),
+        background: asset_server.load(stage.background.as_str()),
+        level: asset_server.load(stage.level.as_str()),
+        par_time: stage.par_time,
+    });
+}
+
+fn wait_for_stage(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    stage_assets: Res<StageAssets>,
+) {
+    let load_state =
+        asset_server.get_group_load_state([stage_assets.background.id, stage_assets.level.id]);
+
+    match load_state {
+        LoadState::Loaded => commands.insert_resource(NextState(GameState::InGame)),
+        LoadState::Failed => {
+            eprintln!("could not load stage {}", stage_assets.name);
+            commands.insert_resource(NextState(GameState::GameOver));
+        }
+        _ => {}
+    }
+}
+
+fn reset_stage_stats(mut commands: Commands) {
+    commands.insert_resource(StageStats::default());
+}
+
+fn tick_stage_stats(
+    mut stats: ResMut<StageStats>,
+    mut escaped_events: EventReader<EnemyEscaped>,
+    time: Res<Time>,
+) {
+    stats.time.tick(time.delta());
+    stats.escaped += escaped_events.iter().count() as u32;
+}
+
+fn setup_stage_clear(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    stats: Res<StageStats>,
+    stage_assets: Res<StageAssets>,
+    core_assets: Res<CoreAssets>,
+    mut score_query: Query<&mut Text, With<UIScore>>,
+) {
+    let seconds = stats.time.elapsed_secs();
+    let time_bonus = ((stage_assets.par_time - seconds).max(0.) * TIME_BONUS_PER_SECOND) as i64;
+    score.amount += time_bonus;
+    for mut scoreboard in score_query.iter_mut() {
+        scoreboard.sections[0].value = score.amount.to_string();
+    }
+
+    let results = format!(
+        "{} clear\n\nKills {}\nEscaped {}\nTime {:.1}s\nTime bonus {}\nScore {}\n\nPress space",
+        stage_assets.name, stats.kills, stats.escaped, seconds, time_bonus, score.amount
+    );
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                results,
+                TextStyle {
+                    font: core_assets.font.clone(),
+                    font_size: 40.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_text_alignment(TextAlignment::CENTER)
+            .with_style(Style {
+                align_self: AlignSelf::Center,
+                margin: UiRect::all(Val::Auto),
+                ..default()
+            }),
+        )
+        .insert(StageClearUi);
+}
+
+fn stage_clear_system(
+    mut commands: Commands,
+    mut progress: ResMut<CampaignProgress>,
+    keys: Res<Input<KeyCode>>,
+    core_assets: Res<CoreAssets>,
+    campaigns: Res<Assets<CampaignAsset>>,
+    stage_clear_ui_query: Query<Entity, With<StageClearUi>>,
+) {
+    if !keys.just_pressed(KeyCode::Space) && !keys.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    for entity in stage_clear_ui_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let stage_count = campaigns
+        .get(&core_assets.campaign)
+        .map_or(0, |campaign| campaign.stages.len());
+
+    progress.stage_index += 1;
+    if progress.stage_index < stage_count {
+        commands.insert_resource(NextState(GameState::LoadingStage));
+    } else {
+        println!("campaign completed");
+        commands.insert_resource(NextState(GameState::GameOver));
+    }
+}
@@ -0,0 +1,380 @@
+use bevy::prelude::{Component, Entity, Vec2};
+
+use crate::spatial_hash::SpatialHash;
+
+/// Size of the default collider box, the size of a sprite
+pub const COLLIDER_SIZE: Vec2 = Vec2::new(16., 16.);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColliderShape {
+    Aabb {
+        size: Vec2,
+    },
+    Circle {
+        radius: f32,
+    },
+    /// Several colliders, their offsets are relative to this one
+    Compound {
+        parts: Vec<Collider>,
+    },
+}
+
+/// Hitbox of an entity, shapes don't rotate with it
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    /// Center of the shape relative to the entity
+    pub offset: Vec2,
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Self::aabb(COLLIDER_SIZE)
+    }
+}
+
+impl Collider {
+    pub fn aabb(size: Vec2) -> Self {
+        Self {
+            shape: ColliderShape::Aabb { size },
+            offset: Vec2::ZERO,
+        }
+    }
+
+    pub fn circle(radius: f32) -> Self {
+        Self {
+            shape: ColliderShape::Circle { radius },
+            offset: Vec2::ZERO,
+        }
+    }
+
+    pub fn compound(parts: Vec<Collider>) -> Self {
+        Self {
+            shape: ColliderShape::Compound { parts },
+            offset: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Bottom left and top right corner of the box around the shape of an
+    /// entity at `position`
+    pub fn bounds(&self, position: Vec2) -> (Vec2, Vec2) {
+        let center = position + self.offset;
+        return match &self.shape {
+            ColliderShape::Aabb { size } => (center - *size / 2., center + *size / 2.),
+            ColliderShape::Circle { radius } => {
+                let half_size = Vec2::new(*radius, *radius);
+                (center - half_size, center + half_size)
+            }
+            ColliderShape::Compound { parts } => parts
+                .iter()
+                .map(|part| part.bounds(center))
+                .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.min(b_min), a_max.max(b_max)))
+                .unwrap_or((center, center)),
+        };
+    }
+
+    /// Adds the box around the shape of an entity at `position` to `grid`
+    pub fn insert_into(&self, grid: &mut SpatialHash, entity: Entity, position: Vec2) {
+        let (min, max) = self.bounds(position);
+        grid.insert(entity, (min + max) / 2., (max - min) / 2.);
+    }
+
+    /// Entities in `grid` near the shape of an entity at `position`, the ones
+    /// worth checking with `overlaps`
+    pub fn nearby(&self, grid: &SpatialHash, position: Vec2) -> Vec<Entity> {
+        return match self.shape {
+            ColliderShape::Circle { radius } => grid.query_circle(position + self.offset, radius),
+            _ => {
+                let (min, max) = self.bounds(position);
+                grid.query_aabb((min + max) / 2., (max - min) / 2.)
+            }
+        };
+    }
+
+    /// Whether the shapes of two entities at the given positions overlap
+    pub fn overlaps(&self, position: Vec2, other: &Collider, other_position: Vec2) -> bool {
+        let center = position + self.offset;
+        let other_center = other_position + other.offset;
+
+        return match (&self.shape, &other.shape) {
+            (ColliderShape::Compound { parts }, _) => parts
+                .iter()
+                .any(|part| part.overlaps(center, other, other_position)),
+            (_, ColliderShape::Compound { parts }) => parts
+                .iter()
+                .any(|part| self.overlaps(position, part, other_center)),
+            (ColliderShape::Aabb { size }, ColliderShape::Aabb { size: other_size }) => (center
+                - other_center)
+                .abs()
+                .cmple((*size + *other_size) / 2.)
+                .all(),
+            (
+                ColliderShape::Circle { radius },
+                ColliderShape::Circle {
+                    radius: other_radius,
+                },
+            ) => center.distance_squared(other_center) <= (radius + other_radius).powi(2),
+            (ColliderShape::Aabb { size }, ColliderShape::Circle { radius }) => {
+                circle_overlaps_aabb(other_center, *radius, center, *size / 2.)
+            }
+            (ColliderShape::Circle { radius }, ColliderShape::Aabb { size }) => {
+                circle_overlaps_aabb(center, *radius, other_center, *size / 2.)
+            }
+        };
+    }
+
+    /// Distance along the ray to where it enters the shape of an entity at
+    /// `position`, see `ray_aabb`. The shape is grown by `radius` to sweep a
+    /// ray that wide.
+    pub fn ray_hit(
+        &self,
+        position: Vec2,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+        radius: f32,
+    ) -> Option<f32> {
+        let center = position + self.offset;
+        return match &self.shape {
+            ColliderShape::Aabb { size } => {
+                ray_aabb(origin, direction, max_distance, center, *size / 2. + radius)
+            }
+            ColliderShape::Circle { radius: own_radius } => {
+                ray_circle(origin, direction, max_distance, center, own_radius + radius)
+            }
+            ColliderShape::Compound { parts } => parts
+                .iter()
+                .filter_map(|part| part.ray_hit(center, origin, direction, max_distance, radius))
+                .min_by(|a, b| a.total_cmp(b)),
+        };
+    }
+}
+
+fn circle_overlaps_aabb(center: Vec2, radius: f32, box_center: Vec2, half_size: Vec2) -> bool {
+    let closest = center.clamp(box_center - half_size, box_center + half_size);
+    return closest.distance_squared(center) <= radius * radius;
+}
+
+/// First collider a ray hits
+pub struct RayHit {
+    pub entity: Entity,
+    /// Distance from the ray origin to `point`
+    pub distance: f32,
+    pub point: Vec2,
+}
+
+/// Distance along the ray to where it enters the box, `None` if it misses
+/// it within `max_distance`. A ray starting inside the box hits at 0.
+pub fn ray_aabb(
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    center: Vec2,
+    half_size: Vec2,
+) -> Option<f32> {
+    let min = center - half_size;
+    let max = center + half_size;
+
+    let mut enter = 0_f32;
+    let mut exit = max_distance;
+    for axis in 0..2 {
+        if direction[axis].abs() < f32::EPSILON {
+            // Parallel to this pair of sides, has to be between them already
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let near = (min[axis] - origin[axis]) / direction[axis];
+        let far = (max[axis] - origin[axis]) / direction[axis];
+        enter = enter.max(near.min(far));
+        exit = exit.min(near.max(far));
+        if enter > exit {
+            return None;
+        }
+    }
+
+    return Some(enter);
+}
+
+/// Like `ray_aabb` for a circle, `direction` has to be normalized
+pub fn ray_circle(
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    center: Vec2,
+    radius: f32,
+) -> Option<f32> {
+    let to_origin = origin - center;
+    let c = to_origin.length_squared() - radius * radius;
+    if c <= 0. {
+        return Some(0.);
+    }
+
+    let b = to_origin.dot(direction);
+    let discriminant = b * b - c;
+    if b > 0. || discriminant < 0. {
+        return None;
+    }
+
+    let distance = -b - discriminant.sqrt();
+    if distance > max_distance {
+        return None;
+    }
+    return Some(distance);
+}
+
+/// Casts a ray `radius` thick against `colliders`, given as entity, position
+/// and collider, and returns the closest hit. `direction` has to be
+/// normalized.
+pub fn ray_cast<'a>(
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f32,
+    radius: f32,
+    colliders: impl Iterator<Item = (Entity, Vec2, &'a Collider)>,
+) -> Option<RayHit> {
+    return colliders
+        .filter_map(|(entity, position, collider)| {
+            collider
+                .ray_hit(position, origin, direction, max_distance, radius)
+                .map(|distance| RayHit {
+                    entity,
+                    distance,
+                    point: origin + direction * distance,
+                })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aabb_overlaps_circle() {
+        let aabb = Collider::aabb(Vec2::new(20., 10.));
+        let circle = Collider::circle(5.);
+
+        assert!(aabb.overlaps(Vec2::ZERO, &circle, Vec2::new(14., 0.)));
+        assert!(circle.overlaps(Vec2::new(14., 0.), &aabb, Vec2::ZERO));
+        assert!(!aabb.overlaps(Vec2::ZERO, &circle, Vec2::new(16., 0.)));
+        // Close to the corner on both axes but not to the corner itself
+        assert!(!aabb.overlaps(Vec2::ZERO, &circle, Vec2::new(14., 9.)));
+        assert!(!circle.overlaps(Vec2::new(14., 9.), &aabb, Vec2::ZERO));
+    }
+
+    #[test]
+    fn circle_overlaps_circle() {
+        let small = Collider::circle(3.);
+        let large = Collider::circle(5.);
+
+        assert!(small.overlaps(Vec2::ZERO, &large, Vec2::new(5., 6.)));
+        assert!(!small.overlaps(Vec2::ZERO, &large, Vec2::new(6., 6.)));
+        // Touching counts
+        assert!(small.overlaps(Vec2::ZERO, &large, Vec2::new(8., 0.)));
+    }
+
+    #[test]
+    fn compound_overlaps_compound() {
+        // Two boxes with a gap between them
+        let gapped = Collider::compound(vec![
+            Collider::aabb(Vec2::splat(4.)).with_offset(Vec2::new(-6., 0.)),
+            Collider::aabb(Vec2::splat(4.)).with_offset(Vec2::new(6., 0.)),
+        ]);
+        let thin = Collider::compound(vec![Collider::circle(1.)]);
+
+        assert!(!gapped.overlaps(Vec2::ZERO, &thin, Vec2::ZERO));
+        assert!(gapped.overlaps(Vec2::ZERO, &thin, Vec2::new(8., 0.)));
+        assert!(thin.overlaps(Vec2::new(-8., 0.), &gapped, Vec2::ZERO));
+        assert!(gapped.overlaps(Vec2::ZERO, &gapped, Vec2::new(12., 0.)));
+        assert!(!gapped.overlaps(Vec2::ZERO, &gapped, Vec2::new(0., 5.)));
+    }
+
+    #[test]
+    fn offsets_move_the_shapes() {
+        let circle = Collider::circle(2.).with_offset(Vec2::new(0., 10.));
+        let aabb = Collider::aabb(Vec2::splat(4.)).with_offset(Vec2::new(-10., 0.));
+
+        assert!(!circle.overlaps(Vec2::ZERO, &aabb, Vec2::ZERO));
+        assert!(circle.overlaps(Vec2::ZERO, &aabb, Vec2::new(10., 10.)));
+
+        // The offset of a compound moves its parts along
+        let compound = Collider::compound(vec![circle.clone()]).with_offset(Vec2::new(5., 0.));
+        assert!(compound.overlaps(Vec2::ZERO, &Collider::circle(1.), Vec2::new(5., 12.)));
+        assert!(!compound.overlaps(Vec2::ZERO, &Collider::circle(1.), Vec2::new(0., 12.)));
+    }
+
+    #[test]
+    fn ray_aabb_hits_misses_and_starts_inside() {
+        let center = Vec2::new(0., 50.);
+        let half_size = Vec2::splat(10.);
+
+        assert_eq!(
+            ray_aabb(Vec2::ZERO, Vec2::Y, 100., center, half_size),
+            Some(40.)
+        );
+        assert_eq!(ray_aabb(Vec2::ZERO, Vec2::X, 100., center, half_size), None);
+        // Out of reach
+        assert_eq!(ray_aabb(Vec2::ZERO, Vec2::Y, 30., center, half_size), None);
+        assert_eq!(ray_aabb(center, Vec2::Y, 100., center, half_size), Some(0.));
+    }
+
+    #[test]
+    fn ray_circle_hits_misses_and_starts_inside() {
+        let center = Vec2::new(0., 50.);
+
+        assert_eq!(
+            ray_circle(Vec2::ZERO, Vec2::Y, 100., center, 10.),
+            Some(40.)
+        );
+        assert_eq!(ray_circle(Vec2::ZERO, Vec2::X, 100., center, 10.), None);
+        // Pointing away
+        assert_eq!(ray_circle(Vec2::ZERO, Vec2::NEG_Y, 100., center, 10.), None);
+        assert_eq!(ray_circle(Vec2::ZERO, Vec2::Y, 30., center, 10.), None);
+        assert_eq!(ray_circle(center, Vec2::Y, 100., center, 10.), Some(0.));
+    }
+
+    #[test]
+    fn ray_cast_returns_the_nearest_hit() {
+        let near = Entity::from_raw(1);
+        let far = Entity::from_raw(2);
+        let aside = Entity::from_raw(3);
+        let aabb = Collider::aabb(Vec2::splat(20.));
+        let circle = Collider::circle(10.);
+        let colliders = [
+            (far, Vec2::new(0., 80.), &aabb),
+            (near, Vec2::new(0., 40.), &circle),
+            (aside, Vec2::new(50., 20.), &aabb),
+        ];
+
+        let hit = ray_cast(Vec2::ZERO, Vec2::Y, 100., 0., colliders.into_iter()).unwrap();
+        assert_eq!(hit.entity, near);
+        assert_eq!(hit.distance, 30.);
+        assert_eq!(hit.point, Vec2::new(0., 30.));
+
+        assert!(ray_cast(Vec2::ZERO, Vec2::X, 30., 0., colliders.into_iter()).is_none());
+    }
+
+    #[test]
+    fn ray_cast_sweeps_its_radius() {
+        let target = Entity::from_raw(1);
+        let aabb = Collider::aabb(Vec2::splat(20.));
+        let circle = Collider::circle(10.);
+
+        // 14 units to the side, 4 past the edge of either shape
+        for collider in [&aabb, &circle] {
+            let colliders = [(target, Vec2::new(14., 50.), collider)];
+            assert!(ray_cast(Vec2::ZERO, Vec2::Y, 100., 2., colliders.into_iter()).is_none());
+
+            let hit = ray_cast(Vec2::ZERO, Vec2::Y, 100., 5., colliders.into_iter()).unwrap();
+            assert_eq!(hit.entity, target);
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use bevy::prelude::{
+    Commands, Component, CoreStage, Entity, EventReader, EventWriter, GlobalTransform, Plugin,
+    Query, ResMut, Transform,
+};
+use iyes_loopless::prelude::{ConditionSet, IntoConditionalSystem};
+
+use crate::{
+    boss::BossPart,
+    components::Collectable,
+    event_system::{CollectionEvent, DamageEvent},
+    pool::{despawn_or_release, EntityPools, Pooled},
+    projectile::{Piercing, Projectile},
+    spatial_hash::SpatialHash,
+    GameState,
+};
+
+mod collider;
+
+pub use collider::{ray_cast, Collider, COLLIDER_SIZE};
+
+/// What an entity is to the collision systems. Pairs are searched from the
+/// lower layer, so the crowded bullet layers come last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CollisionLayer {
+    Player,
+    Enemy,
+    Pickup,
+    Terrain,
+    PlayerBullet,
+    EnemyBullet,
+}
+
+impl CollisionLayer {
+    const ALL: [CollisionLayer; 6] = [
+        CollisionLayer::Player,
+        CollisionLayer::Enemy,
+        CollisionLayer::Pickup,
+        CollisionLayer::Terrain,
+        CollisionLayer::PlayerBullet,
+        CollisionLayer::EnemyBullet,
+    ];
+
+    pub fn bit(self) -> u32 {
+        return 1 << self as u32;
+    }
+
+    /// Layers this one interacts with, unless the entity sets its own mask
+    pub fn default_mask(self) -> u32 {
+        return match self {
+            CollisionLayer::Player => {
+                CollisionLayer::EnemyBullet.bit()
+                    | CollisionLayer::Pickup.bit()
+                    | CollisionLayer::Terrain.bit()
+            }
+            CollisionLayer::Enemy => CollisionLayer::PlayerBullet.bit(),
+            CollisionLayer::Pickup => CollisionLayer::Player.bit(),
+            CollisionLayer::Terrain => {
+                CollisionLayer::Player.bit()
+                    | CollisionLayer::PlayerBullet.bit()
+                    | CollisionLayer::EnemyBullet.bit()
+            }
+            CollisionLayer::PlayerBullet => {
+                CollisionLayer::Enemy.bit() | CollisionLayer::Terrain.bit()
+            }
+            CollisionLayer::EnemyBullet => {
+                CollisionLayer::Player.bit() | CollisionLayer::Terrain.bit()
+            }
+        };
+    }
+}
+
+/// Layer of a `Collider` and the layers it interacts with. Two colliders
+/// only collide when each one's mask has the other's layer.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionLayers {
+    pub layer: CollisionLayer,
+    pub mask: u32,
+}
+
+impl CollisionLayers {
+    pub fn new(layer: CollisionLayer) -> Self {
+        Self {
+            layer,
+            mask: layer.default_mask(),
+        }
+    }
+
+    pub fn interacts(&self, other: &CollisionLayers) -> bool {
+        return self.mask & other.layer.bit() != 0 && other.mask & self.layer.bit() != 0;
+    }
+}
+
+/// Sent every frame two interacting colliders overlap, `a` is the one on
+/// the lower layer
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub a_layer: CollisionLayer,
+    pub b: Entity,
+    pub b_layer: CollisionLayer,
+}
+
+/// Cells of the collision grids, about twice the collider size
+const GRID_CELL_SIZE: f32 = 32.;
+
+/// A spatial hash per collision layer
+pub struct CollisionGrid {
+    layers: Vec<SpatialHash>,
+}
+
+impl Default for CollisionGrid {
+    fn default() -> Self {
+        Self {
+            layers: CollisionLayer::ALL
+                .iter()
+                .map(|_| SpatialHash::new(GRID_CELL_SIZE))
+                .collect(),
+        }
+    }
+}
+
+impl CollisionGrid {
+    pub fn layer(&self, layer: CollisionLayer) -> &SpatialHash {
+        return &self.layers[layer as usize];
+    }
+}
+
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<CollisionGrid>()
+            .add_event::<CollisionEvent>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                detect_collisions.run_in_state(GameState::InGame),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .with_system(projectile_hits)
+                    .with_system(pickups)
+                    .into(),
+            );
+    }
+}
+
+// Positions are those at the end of the last frame, the same for every pair
+fn detect_collisions(
+    mut grid: ResMut<CollisionGrid>,
+    colliders_query: Query<(Entity, &GlobalTransform, &Collider, &CollisionLayers)>,
+    mut collision_events: EventWriter<CollisionEvent>,
+) {
+    for layer in grid.layers.iter_mut() {
+        layer.clear();
+    }
+    for (entity, transform, collider, layers) in colliders_query.iter() {
+        collider.insert_into(
+            &mut grid.layers[layers.layer as usize],
+            entity,
+            transform.translation().truncate(),
+        );
+    }
+
+    for (entity, transform, collider, layers) in colliders_query.iter() {
+        let position = transform.translation().truncate();
+
+        for other_layer in CollisionLayer::ALL {
+            // Lower layers find their pairs with this one themselves
+            if (other_layer as usize) < (layers.layer as usize)
+                || layers.mask & other_layer.bit() == 0
+            {
+                continue;
+            }
+
+            for other in collider.nearby(grid.layer(other_layer), position) {
+                // Pairs within a layer are found from both sides
+                if other_layer == layers.layer && other.id() <= entity.id() {
+                    continue;
+                }
+                let (_, other_transform, other_collider, other_layers) =
+                    match colliders_query.get(other) {
+                        Ok(other) => other,
+                        Err(_) => continue,
+                    };
+                if !layers.interacts(other_layers)
+                    || !collider.overlaps(
+                        position,
+                        other_collider,
+                        other_transform.translation().truncate(),
+                    )
+                {
+                    continue;
+                }
+
+                collision_events.send(CollisionEvent {
+                    a: entity,
+                    a_layer: layers.layer,
+                    b: other,
+                    b_layer: other_layer,
+                });
+            }
+        }
+    }
+}
+
+fn is_bullet(layer: CollisionLayer) -> bool {
+    return matches!(
+        layer,
+        CollisionLayer::PlayerBullet | CollisionLayer::EnemyBullet
+    );
+}
+
+/// Bullets damage what they hit, terrain only stops them
+fn projectile_hits(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut projectiles_query: Query<(
+        &Projectile,
+        &Transform,
+        Option<&mut Piercing>,
+        Option<&Pooled>,
+    )>,
+    boss_part_query: Query<&BossPart>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut pools: ResMut<EntityPools>,
+) {
+    // Projectiles that already hit something this frame
+    let mut spent = Vec::new();
+
+    for event in collision_events.iter() {
+        // Bullets come after what they hit
+        if !is_bullet(event.b_layer) || is_bullet(event.a_layer) {
+            continue;
+        }
+        let (projectile_entity, hit_entity) = (event.b, event.a);
+        if spent.contains(&projectile_entity) {
+            continue;
+        }
+        let (projectile, projectile_tranform, piercing, pooled) =
+            match projectiles_query.get_mut(projectile_entity) {
+                Ok(projectile) => projectile,
+                Err(_) => continue,
+            };
+
+        // Never hits whoever fired it
+        if hit_entity == projectile.source {
+            continue;
+        }
+
+        // Boss parts share the health of the boss
+        let target = boss_part_query
+            .get(hit_entity)
+            .map_or(hit_entity, |part| part.boss);
+
+        match piercing {
+            Some(mut piercing) => {
+                if piercing.hit.contains(&target) {
+                    continue;
+                }
+                piercing.hit.push(target);
+            }
+            None => {
+                spent.push(projectile_entity);
+                despawn_or_release(&mut commands, &mut pools, projectile_entity, pooled);
+            }
+        }
+
+        if event.a_layer != CollisionLayer::Terrain {
+            damage_events.send(DamageEvent {
+                damage: projectile.damage,
+                target,
+                translation: projectile_tranform.translation,
+            });
+        }
+    }
+}
+
+fn pickups(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    collectables_query: Query<(&Collectable, Option<&Pooled>)>,
+    mut collection_events: EventWriter<CollectionEvent>,
+    mut pools: ResMut<EntityPools>,
+) {
+    for event in collision_events.iter() {
+        if event.a_layer != CollisionLayer::Player || event.b_layer != CollisionLayer::Pickup {
+            continue;
+        }
+
+        if let Ok((&collectable, pooled)) = collectables_query.get(event.b) {
+            despawn_or_release(&mut commands, &mut pools, event.b, pooled);
+            collection_events.send(CollectionEvent {
+                collectable,
+                collector: event.a,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mask(layers: &[CollisionLayer]) -> u32 {
+        return layers.iter().fold(0, |mask, layer| mask | layer.bit());
+    }
+
+    #[test]
+    fn default_masks() {
+        use CollisionLayer::*;
+
+        assert_eq!(Player.default_mask(), mask(&[EnemyBullet, Pickup, Terrain]));
+        assert_eq!(Enemy.default_mask(), mask(&[PlayerBullet]));
+        assert_eq!(Pickup.default_mask(), mask(&[Player]));
+        assert_eq!(
+            Terrain.default_mask(),
+            mask(&[Player, PlayerBullet, EnemyBullet])
+        );
+        assert_eq!(PlayerBullet.default_mask(), mask(&[Enemy, Terrain]));
+        assert_eq!(EnemyBullet.default_mask(), mask(&[Player, Terrain]));
+    }
+
+    #[test]
+    fn default_masks_interact_both_ways() {
+        for layer in CollisionLayer::ALL {
+            for other in CollisionLayer::ALL {
+                let (a, b) = (CollisionLayers::new(layer), CollisionLayers::new(other));
+                assert_eq!(a.interacts(&b), b.interacts(&a), "{:?} {:?}", layer, other);
+                assert_eq!(
+                    a.interacts(&b),
+                    layer.default_mask() & other.bit() != 0,
+                    "{:?} {:?}",
+                    layer,
+                    other
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bullets_only_hit_the_other_side() {
+        use CollisionLayer::*;
+        let interacts = |a, b| CollisionLayers::new(a).interacts(&CollisionLayers::new(b));
+
+        assert!(interacts(Enemy, PlayerBullet));
+        assert!(interacts(Player, EnemyBullet));
+        assert!(!interacts(Player, PlayerBullet));
+        assert!(!interacts(Enemy, EnemyBullet));
+        assert!(!interacts(PlayerBullet, EnemyBullet));
+        assert!(!interacts(Pickup, PlayerBullet));
+    }
+
+    #[test]
+    fn both_masks_have_to_agree() {
+        let player = CollisionLayers::new(CollisionLayer::Player);
+        // Ignores the player although the player's mask has pickups
+        let ghost_pickup = CollisionLayers {
+            layer: CollisionLayer::Pickup,
+            mask: 0,
+        };
+
+        assert!(!player.interacts(&ghost_pickup));
+        assert!(!ghost_pickup.interacts(&player));
+    }
+}
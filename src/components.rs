@@ -14,5 +14,9 @@ impl Health {
 #[derive(Component)]
 pub struct Background;
 
-#[derive(Component)]
-pub struct Collectable;
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collectable {
+    Coin,
+    /// Raises the weapon level of the player
+    PowerUp,
+}
@@ -1,19 +1,50 @@
-use bevy::{prelude::*, time::Stopwatch};
+use bevy::prelude::*;
 use iyes_loopless::prelude::AppLooplessStateExt;
+use serde::Deserialize;
 
 use crate::{
-    collision::Collider,
+    collision::{Collider, CollisionLayer, CollisionLayers},
     components::Health,
     entities::entity_loader::craete_entity_from_atlas,
     moveable::Moveable,
-    movement::{self, path_movement::PathMoveable},
-    shoot::Shootable,
-    CoreAssets, GameState,
+    movement, CoreAssets, GameState,
 };
 
 #[derive(Component)]
 pub struct Enemy;
 
+#[derive(Component, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EnemyType {
+    #[default]
+    Plane,
+    Heavy,
+}
+
+impl EnemyType {
+    fn health(&self) -> i32 {
+        match self {
+            EnemyType::Plane => 1,
+            EnemyType::Heavy => 5,
+        }
+    }
+
+    /// Planes are hit on their body and wings only, heavies on the whole sprite
+    fn collider(&self) -> Collider {
+        match self {
+            EnemyType::Plane => Collider::aabb(Vec2::new(14., 12.)),
+            EnemyType::Heavy => Collider::default(),
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            EnemyType::Plane => Color::WHITE,
+            EnemyType::Heavy => Color::rgb(1., 0.6, 0.6),
+        }
+    }
+}
+
 // pub struct EnemyPlugin;
 
 // impl Plugin for EnemyPlugin {
@@ -25,29 +56,29 @@ pub struct Enemy;
 pub fn spawn_enemy(
     commands: &mut Commands,
     core_asssets: &Res<CoreAssets>,
-    move_positions_array: Vec<Vec2>,
-    movement_speed: f32,
-) {
-    let enemy_entity = craete_entity_from_atlas(
-        commands,
-        &core_asssets.plane,
-        0,
-        move_positions_array[0].extend(100.),
-    );
+    position: Vec2,
+    enemy_type: EnemyType,
+    health_multiplier: f32,
+) -> Entity {
+    let enemy_entity =
+        craete_entity_from_atlas(commands, &core_asssets.plane, 0, position.extend(100.));
+
+    let health = (enemy_type.health() as f32 * health_multiplier)
+        .round()
+        .max(1.) as i32;
+
+    let mut sprite = TextureAtlasSprite::new(0);
+    sprite.color = enemy_type.color();
 
     commands
         .entity(enemy_entity)
         .insert(Name::new(format!("Enemy_{}", enemy_entity.id())))
         .insert(Enemy)
-        .insert(Health { amount: 1 })
-        .insert(Collider)
-        .insert(PathMoveable { next_path_index: 0, move_positions: move_positions_array, movement_speed: movement_speed })
-        // .insert(Shootable {
-        //     direction: Vec3::new(0., -1., 0.),
-        //     source: enemy_entity,
-        //     shoot_speed_per_ms: 500,
-        //     time: Stopwatch::new(),
-        //     is_shooting: true,
-        // })
-        ;
+        .insert(enemy_type)
+        .insert(sprite)
+        .insert(Health { amount: health })
+        .insert(enemy_type.collider())
+        .insert(CollisionLayers::new(CollisionLayer::Enemy));
+
+    return enemy_entity;
 }
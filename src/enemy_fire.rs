@@ -0,0 +1,256 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, time::Stopwatch};
+use bevy_pixel_camera::PixelProjection;
+use iyes_loopless::prelude::ConditionSet;
+use serde::Deserialize;
+
+use crate::{
+    offscreen::visible_area, player::Player, pool::EntityPools, shoot::spawn_projectile,
+    weapon::WeaponsAsset, CoreAssets, GameState,
+};
+
+// Data =========================================================
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulletPattern {
+    /// `count` shots over `spread` degrees centred on the player
+    Aimed {
+        #[serde(default = "default_count")]
+        count: usize,
+        #[serde(default)]
+        spread: f32,
+    },
+    /// `count` shots evenly around the shooter
+    Ring { count: usize },
+    /// `arms` streams evenly around the shooter, meant to be turned with
+    /// `rotation_speed`
+    Spiral { arms: usize },
+    /// `count` shots over `spread` degrees centred on `direction`
+    Fan {
+        count: usize,
+        spread: f32,
+        #[serde(default = "default_direction")]
+        direction: [f32; 2],
+    },
+    /// One shot along `direction`
+    Stream {
+        #[serde(default = "default_direction")]
+        direction: [f32; 2],
+    },
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_direction() -> [f32; 2] {
+    [0., -1.]
+}
+
+/// One step of a pattern script, steps play in order and loop
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct PatternStep {
+    /// Weapon id from the weapons file, gives the projectile
+    pub weapon: String,
+    pub pattern: BulletPattern,
+    /// Milliseconds between two shots of the pattern
+    pub interval_ms: u64,
+    /// Seconds before the next step starts
+    pub duration: f32,
+    /// Degrees per second the pattern turns while the step lasts
+    #[serde(default)]
+    pub rotation_speed: f32,
+}
+
+impl PatternStep {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.weapon.is_empty() {
+            return Err("weapon must not be empty".to_string());
+        }
+        if self.interval_ms == 0 {
+            return Err("interval_ms must be greater than 0".to_string());
+        }
+        if self.duration.is_nan() || self.duration <= 0. {
+            return Err("duration must be greater than 0".to_string());
+        }
+        if self.rotation_speed.is_nan() {
+            return Err("rotation_speed must be a number".to_string());
+        }
+
+        let count = match self.pattern {
+            BulletPattern::Aimed { count, .. } => count,
+            BulletPattern::Ring { count } => count,
+            BulletPattern::Spiral { arms } => arms,
+            BulletPattern::Fan { count, .. } => count,
+            BulletPattern::Stream { .. } => 1,
+        };
+        if count == 0 {
+            return Err("pattern needs at least one shot".to_string());
+        }
+        return Ok(());
+    }
+}
+
+impl BulletPattern {
+    /// Shot directions, `to_player` is `None` when there is no player to aim at
+    fn directions(&self, to_player: Option<Vec2>, rotation: f32) -> Vec<Vec2> {
+        let rotate = |direction: Vec2| Mat2::from_angle(rotation) * direction;
+
+        return match *self {
+            BulletPattern::Aimed { count, spread } => {
+                let aim = to_player
+                    .and_then(|to_player| to_player.try_normalize())
+                    .unwrap_or(Vec2::NEG_Y);
+                fan(rotate(aim), count, spread.to_radians())
+            }
+            BulletPattern::Ring { count } => around(rotate(Vec2::NEG_Y), count),
+            BulletPattern::Spiral { arms } => around(rotate(Vec2::NEG_Y), arms),
+            BulletPattern::Fan {
+                count,
+                spread,
+                direction,
+            } => fan(
+                rotate(Vec2::from(direction).normalize_or_zero()),
+                count,
+                spread.to_radians(),
+            ),
+            BulletPattern::Stream { direction } => {
+                vec![rotate(Vec2::from(direction).normalize_or_zero())]
+            }
+        };
+    }
+}
+
+fn fan(center: Vec2, count: usize, spread: f32) -> Vec<Vec2> {
+    if count <= 1 {
+        return vec![center];
+    }
+
+    let step = spread / (count - 1) as f32;
+    return (0..count)
+        .map(|index| Mat2::from_angle(-spread / 2. + step * index as f32) * center)
+        .collect();
+}
+
+fn around(start: Vec2, count: usize) -> Vec<Vec2> {
+    let step = TAU / count.max(1) as f32;
+    return (0..count)
+        .map(|index| Mat2::from_angle(step * index as f32) * start)
+        .collect();
+}
+// =============================================================
+
+/// Fires a pattern script while the entity is on screen
+#[derive(Component)]
+pub struct BulletEmitter {
+    pub script: Vec<PatternStep>,
+    /// Scales how often shots are fired, 2 fires twice as often
+    pub density: f32,
+    step_index: usize,
+    step_time: Stopwatch,
+    fire_time: Stopwatch,
+    /// Radians the current step has turned
+    rotation: f32,
+}
+
+impl BulletEmitter {
+    pub fn new(script: Vec<PatternStep>, density: f32) -> Self {
+        Self {
+            script,
+            density,
+            step_index: 0,
+            step_time: Stopwatch::new(),
+            fire_time: Stopwatch::new(),
+            rotation: 0.,
+        }
+    }
+}
+
+pub struct EnemyFirePlugin;
+
+impl Plugin for EnemyFirePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::InGame)
+                .with_system(emit_bullets)
+                .into(),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn emit_bullets(
+    mut commands: Commands,
+    mut emitter_query: Query<(Entity, &GlobalTransform, &mut BulletEmitter)>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    camera_query: Query<(&Transform, &PixelProjection)>,
+    mut pools: ResMut<EntityPools>,
+    core_asssets: Res<CoreAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
+    time: Res<Time>,
+) {
+    let weapons = match weapons_assets.get(&core_asssets.weapons) {
+        Some(weapons) => weapons,
+        None => return,
+    };
+    let (min, max) = match camera_query.get_single() {
+        Ok((transform, projection)) => visible_area(transform, projection),
+        Err(_) => return,
+    };
+    let player = player_query
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation().truncate());
+
+    for (entity, transform, mut emitter) in emitter_query.iter_mut() {
+        if emitter.script.is_empty() || emitter.density <= 0. {
+            continue;
+        }
+
+        // Next step of the script
+        emitter.step_time.tick(time.delta());
+        if emitter.step_time.elapsed_secs() >= emitter.script[emitter.step_index].duration {
+            emitter.step_index = (emitter.step_index + 1) % emitter.script.len();
+            emitter.step_time.reset();
+            emitter.rotation = 0.;
+        }
+
+        let step = emitter.script[emitter.step_index].clone();
+        emitter.rotation += step.rotation_speed.to_radians() * time.delta_seconds();
+
+        // Shots from off screen can't be dodged
+        let position = transform.translation().truncate();
+        if position.cmplt(min).any() || position.cmpgt(max).any() {
+            continue;
+        }
+
+        emitter.fire_time.tick(time.delta());
+        let interval = step.interval_ms as f32 / 1000. / emitter.density;
+        if emitter.fire_time.elapsed_secs() < interval {
+            continue;
+        }
+        emitter.fire_time.reset();
+
+        // Unknown ids are reported once by `check_weapon_ids`
+        let weapon = match weapons.weapons.get(&step.weapon) {
+            Some(weapon) => weapon,
+            None => continue,
+        };
+
+        let to_player = player.map(|player| player - position);
+        for direction in step.pattern.directions(to_player, emitter.rotation) {
+            spawn_projectile(
+                &mut commands,
+                &mut pools,
+                &core_asssets,
+                weapon,
+                position,
+                direction,
+                entity,
+                true,
+            );
+        }
+    }
+}
@@ -28,18 +28,29 @@ pub fn craete_entity_from_atlas(
     index: usize,
     translation: Vec3,
 ) -> Entity {
+    let entity = commands.spawn().id();
+    insert_atlas_bundle(commands, entity, atlas, index, translation);
+    return entity;
+}
+
+/// Gives `entity` a sprite from `atlas`, replacing the one of a reused entity
+pub fn insert_atlas_bundle(
+    commands: &mut Commands,
+    entity: Entity,
+    atlas: &Handle<TextureAtlas>,
+    index: usize,
+    translation: Vec3,
+) {
     let mut sprite = TextureAtlasSprite::new(index);
     sprite.color = Color::WHITE;
 
-    return commands
-        .spawn_bundle(SpriteSheetBundle {
-            sprite: sprite,
-            texture_atlas: atlas.clone(),
-            transform: Transform {
-                translation: translation,
-                ..Default::default()
-            },
+    commands.entity(entity).insert_bundle(SpriteSheetBundle {
+        sprite: sprite,
+        texture_atlas: atlas.clone(),
+        transform: Transform {
+            translation: translation,
             ..Default::default()
-        })
-        .id();
+        },
+        ..Default::default()
+    });
 }
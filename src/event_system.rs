@@ -1,11 +1,19 @@
 use crate::{
-    animation::{spawn_animated_entity, AnimationSheet},
-    collision::Collider,
+    animation::{insert_animated_bundle, AnimationSheet},
+    campaign::StageStats,
+    collision::{Collider, CollisionLayer, CollisionLayers},
     components::{Collectable, Health},
+    moveable::{Moveable, RotationMode},
+    offscreen::DespawnOffscreen,
+    player::Player,
+    pool::{EntityPools, PoolKind},
+    shoot::Shootable,
+    utils::load_config::ConfigData,
+    weapon::WeaponsAsset,
     CoreAssets, GameState, Score, UIScore,
 };
 use bevy::prelude::*;
-use iyes_loopless::prelude::ConditionSet;
+use iyes_loopless::{prelude::ConditionSet, state::NextState};
 use rand::Rng;
 
 // Events ========================================
@@ -15,9 +23,30 @@ pub struct DamageEvent {
     pub target: Entity,
     pub translation: Vec3,
 }
-pub struct CollectionEvent;
+pub struct CollectionEvent {
+    pub collectable: Collectable,
+    pub collector: Entity,
+}
+/// Sent when the stage's boss is destroyed
+pub struct StageClearEvent;
+/// Sent when an enemy leaves the end of its path without being destroyed
+pub struct EnemyEscaped {
+    pub entity: Entity,
+}
+/// Sent when an enemy's health runs out, before it is despawned
+pub struct EnemyDestroyed {
+    pub entity: Entity,
+}
 // ================================================
 
+/// Collectables burst out of destroyed enemies and slow down
+const COLLECTABLE_SCATTER_SPEED: f32 = 80.;
+const COLLECTABLE_DRAG: f32 = 3.;
+const COLLECTABLE_OFFSCREEN_MARGIN: f32 = 10.;
+/// Chance of a destroyed enemy dropping a power-up
+const POWER_UP_DROP_CHANCE: f64 = 0.1;
+const POWER_UP_COLOR: Color = Color::rgb(0.4, 0.7, 1.);
+
 pub struct EventSystemPlugin;
 
 impl Plugin for EventSystemPlugin {
@@ -30,16 +59,25 @@ impl Plugin for EventSystemPlugin {
                 .into(),
         )
         .add_event::<DamageEvent>()
-        .add_event::<CollectionEvent>();
+        .add_event::<CollectionEvent>()
+        .add_event::<StageClearEvent>()
+        .add_event::<EnemyEscaped>()
+        .add_event::<EnemyDestroyed>();
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn process_damage_events(
     mut commands: Commands,
     mut damage_events: EventReader<DamageEvent>,
+    mut destroyed_events: EventWriter<EnemyDestroyed>,
     mut health_query: Query<&mut Health>,
+    mut player_query: Query<&mut Shootable, With<Player>>,
+    config: Res<ConfigData>,
+    mut pools: ResMut<EntityPools>,
     core_assets: Res<CoreAssets>,
     mut score: ResMut<Score>,
+    mut stats: ResMut<StageStats>,
     mut score_query: Query<&mut Text, With<UIScore>>,
 ) {
     for &DamageEvent {
@@ -49,15 +87,41 @@ fn process_damage_events(
     } in damage_events.iter()
     {
         if let Ok(mut health) = health_query.get_mut(target) {
+            // Already destroyed by an earlier event this frame
+            if health.amount <= 0 {
+                continue;
+            }
             health.take_damage(damage);
-            if (health.amount - damage) <= 0 {
-                commands.entity(target).despawn();
+
+            if let Ok(mut shootable) = player_query.get_mut(target) {
+                if health.amount <= 0 {
+                    // Dying costs a weapon level
+                    let had_level = shootable.level > 1;
+                    shootable.level = (shootable.level - 1).max(1);
+
+                    if had_level && config.player.weapon_levels_as_lives {
+                        health.amount = config.player.base_health;
+                    } else {
+                        commands.insert_resource(NextState(GameState::GameOver));
+                    }
+                }
+                continue;
+            }
+
+            if health.amount <= 0 {
+                commands.entity(target).despawn_recursive();
+                destroyed_events.send(EnemyDestroyed { entity: target });
+                stats.kills += 1;
 
                 let random_number = rand::thread_rng().gen_range(0..5);
                 for index in 0..random_number {
                     let random_position = rand::thread_rng().gen_range(0..50);
-                    let collectable = spawn_animated_entity(
+                    let scatter_angle = rand::thread_rng().gen_range(0.0..std::f32::consts::TAU);
+                    let scatter_direction = Mat2::from_angle(scatter_angle) * Vec2::X;
+                    let collectable = pools.acquire(&mut commands, PoolKind::Collectable);
+                    insert_animated_bundle(
                         &mut commands,
+                        collectable,
                         translation + Vec3::new(random_position as f32, random_position as f32, 0.),
                         &AnimationSheet {
                             handle: core_assets.collectable.clone(),
@@ -65,32 +129,97 @@ fn process_damage_events(
                         },
                         0.2,
                         true,
+                        Color::WHITE,
                     );
 
                     commands
                         .entity(collectable)
                         .insert(Name::new("Collectable"))
-                        .insert(Collectable)
-                        .insert(Collider);
+                        .insert(Collectable::Coin)
+                        .insert(Collider::circle(config.player.pickup_radius))
+                        .insert(CollisionLayers::new(CollisionLayer::Pickup))
+                        .insert(
+                            Moveable::new(scatter_direction.extend(0.), COLLECTABLE_SCATTER_SPEED)
+                                .with_drag(COLLECTABLE_DRAG)
+                                .with_rotation_mode(RotationMode::Keep),
+                        )
+                        .insert(DespawnOffscreen::new(COLLECTABLE_OFFSCREEN_MARGIN));
                 }
-            }
 
-            let animation_sheet = AnimationSheet {
-                handle: core_assets.general.clone(),
-                frames: vec![4, 8, 9],
-            };
+                if rand::thread_rng().gen_bool(POWER_UP_DROP_CHANCE) {
+                    let power_up = pools.acquire(&mut commands, PoolKind::Collectable);
+                    insert_animated_bundle(
+                        &mut commands,
+                        power_up,
+                        translation,
+                        &AnimationSheet {
+                            handle: core_assets.collectable.clone(),
+                            frames: vec![0, 1, 2, 3, 4],
+                        },
+                        0.1,
+                        true,
+                        POWER_UP_COLOR,
+                    );
+
+                    commands
+                        .entity(power_up)
+                        .insert(Name::new("PowerUp"))
+                        .insert(Collectable::PowerUp)
+                        .insert(Collider::circle(config.player.pickup_radius))
+                        .insert(CollisionLayers::new(CollisionLayer::Pickup))
+                        .insert(
+                            Moveable::new(Vec3::new(0., -1., 0.), COLLECTABLE_SCATTER_SPEED / 2.)
+                                .with_rotation_mode(RotationMode::Keep),
+                        )
+                        .insert(DespawnOffscreen::new(COLLECTABLE_OFFSCREEN_MARGIN));
+                }
+
+                let animation_sheet = AnimationSheet {
+                    handle: core_assets.general.clone(),
+                    frames: vec![4, 8, 9],
+                };
 
-            let _ = spawn_animated_entity(&mut commands, translation, &animation_sheet, 0.1, false);
+                let explosion = pools.acquire(&mut commands, PoolKind::Effect);
+                insert_animated_bundle(
+                    &mut commands,
+                    explosion,
+                    translation,
+                    &animation_sheet,
+                    0.1,
+                    false,
+                    Color::WHITE,
+                );
 
-            score.amount += 100;
-            let mut scoreboard = score_query.single_mut();
-            scoreboard.sections[0].value = score.amount.to_string();
+                score.amount += 100;
+                let mut scoreboard = score_query.single_mut();
+                scoreboard.sections[0].value = score.amount.to_string();
+            }
         }
     }
 }
 
-fn process_collection_events(mut commands: Commands, mut events: EventReader<CollectionEvent>) {
-    for &CollectionEvent in events.iter() {
-        println!("collection event");
+fn process_collection_events(
+    mut events: EventReader<CollectionEvent>,
+    mut shootable_query: Query<&mut Shootable>,
+    core_assets: Res<CoreAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
+) {
+    for &CollectionEvent {
+        collectable,
+        collector,
+    } in events.iter()
+    {
+        match collectable {
+            Collectable::Coin => {}
+            Collectable::PowerUp => {
+                if let Ok(mut shootable) = shootable_query.get_mut(collector) {
+                    let max_level = weapons_assets
+                        .get(&core_assets.weapons)
+                        .and_then(|weapons| weapons.weapons.get(&shootable.weapon))
+                        .map_or(1, |weapon| weapon.max_level());
+                    shootable.level = (shootable.level + 1).min(max_level);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use iyes_loopless::prelude::ConditionSet;
+
+use crate::{enemy::Enemy, moveable::Moveable, GameState};
+
+/// Turns a `Moveable` projectile towards the nearest enemy
+#[derive(Component)]
+pub struct Homing {
+    /// Radians per second
+    pub turn_rate: f32,
+    pub target: Option<Entity>,
+}
+
+pub struct HomingPlugin;
+
+impl Plugin for HomingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::InGame)
+                .with_system(home_in)
+                .into(),
+        );
+    }
+}
+
+fn home_in(
+    mut homing_query: Query<(&Transform, &mut Moveable, &mut Homing)>,
+    enemy_query: Query<(Entity, &GlobalTransform), With<Enemy>>,
+    time: Res<Time>,
+) {
+    for (transform, mut moveable, mut homing) in homing_query.iter_mut() {
+        let position = transform.translation.truncate();
+
+        // Pick a new target when there is none or it has been destroyed
+        let target_position = match homing
+            .target
+            .and_then(|target| enemy_query.get(target).ok())
+        {
+            Some((_, target_transform)) => Some(target_transform.translation().truncate()),
+            None => {
+                let nearest = enemy_query.iter().min_by(|(_, a), (_, b)| {
+                    let a = a.translation().truncate().distance_squared(position);
+                    let b = b.translation().truncate().distance_squared(position);
+                    a.total_cmp(&b)
+                });
+                homing.target = nearest.map(|(entity, _)| entity);
+                nearest.map(|(_, target_transform)| target_transform.translation().truncate())
+            }
+        };
+
+        let to_target = match target_position.and_then(|target| (target - position).try_normalize())
+        {
+            Some(to_target) => to_target,
+            None => continue,
+        };
+
+        let direction = moveable.direction.truncate().normalize_or_zero();
+        if direction == Vec2::ZERO {
+            moveable.direction = to_target.extend(moveable.direction.z);
+            continue;
+        }
+
+        let max_turn = homing.turn_rate * time.delta_seconds();
+        let turn = direction
+            .angle_between(to_target)
+            .clamp(-max_turn, max_turn);
+        moveable.direction = (Mat2::from_angle(turn) * direction).extend(moveable.direction.z);
+    }
+}
@@ -3,6 +3,7 @@ use leafwing_input_manager::Actionlike;
 #[derive(Actionlike, PartialEq, Eq, Clone, Copy, Hash, Debug)]
 pub enum InputAction {
     Shoot,
+    Shoot_Secondary,
     Move_Up,
     Move_Down,
     Move_Left,
@@ -1,55 +1,74 @@
 use animation::AnimatorPlugin;
+use beam::BeamPlugin;
 use bevy::{
     prelude::*,
     sprite::{Anchor, Rect},
-    time::Stopwatch,
     ui::FocusPolicy,
     window::{PresentMode, WindowMode},
 };
 use bevy_asset_loader::prelude::*;
 use bevy_editor_pls::EditorPlugin;
 use bevy_pixel_camera::{PixelCameraBundle, PixelCameraPlugin};
+use boss::BossPlugin;
+use campaign::{CampaignAsset, CampaignPlugin, CampaignProgress, StageAssets};
 use collision::CollisionPlugin;
 use components::Background;
-use entities::entity_loader::spawn_entity;
+use enemy_fire::EnemyFirePlugin;
+use entities::entity_loader::{craete_entity_from_atlas, spawn_entity};
 use event_system::EventSystemPlugin;
+use homing::HomingPlugin;
 use input_actions::InputAction;
 use iyes_loopless::prelude::*;
 use leafwing_input_manager::prelude::*;
 use moveable::MoveablePlugin;
-use movement::path_movement::{PathMoveable, PathMovementPlugin};
+use movement::{
+    path_movement::{PathMoveable, PathMovementPlugin},
+    steering::SteeringPlugin,
+};
+use offscreen::OffscreenPlugin;
 use player::PlayerPlugin;
+use pool::{EntityPools, PoolPlugin};
 
 // use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy_inspector_egui::WorldInspectorPlugin;
 use shoot::ShootPlugin;
 use spawners::enemy_wave_spawner::EnemyWaveSpawnerPlugin;
-use utils::load_config::ConfigData;
+use utils::{config_watcher::ConfigWatcherPlugin, load_config::ConfigData};
+use weapon::{WeaponPlugin, WeaponsAsset};
 
 use crate::{
     animation::{spawn_animated_entity, spawn_animated_entity_with_color, AnimationSheet},
-    collision::Collider,
+    collision::{Collider, CollisionLayer, CollisionLayers},
     components::{Collectable, Health},
-    player::Player,
+    player::{Charge, ChargeIndicator, Player, SecondaryWeapon},
     shoot::Shootable,
     utils::load_config::load_config,
 };
 
 pub const BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
 
+mod beam;
+mod boss;
+mod campaign;
 mod enemy;
+mod enemy_fire;
 mod entities;
 mod event_system;
+mod homing;
 mod input_actions;
 mod moveable;
+mod offscreen;
 mod player;
+mod pool;
 mod projectile;
 mod shoot;
+mod spatial_hash;
 // TODO Generic file for components, maybe replace this in the future
 mod animation;
 mod components;
 mod plugins;
 mod utils;
+mod weapon;
 // mod asset_collections;
 mod collision;
 mod movement;
@@ -60,7 +79,11 @@ enum GameState {
     LoadingMainMenu,
     MainMenu,
     LoadingInGame,
+    /// Loads the background and level of the current campaign stage
+    LoadingStage,
     InGame,
+    /// Results of a cleared stage before moving on to the next one
+    StageClear,
     GameOver,
 }
 
@@ -80,12 +103,19 @@ pub struct CoreAssets {
     #[asset(path = "player.png")]
     pub plane: Handle<TextureAtlas>,
 
+    #[asset(texture_atlas(tile_size_x = 8., tile_size_y = 8., columns = 3, rows = 1))]
+    #[asset(path = "Sheets/Projectiles/Missile/Missile-Spritesheet.png")]
+    pub missile: Handle<TextureAtlas>,
+
     // TODO implement this by copying the player sprite and setting it to black
     // #[asset(texture_atlas(tile_size_x = 16., tile_size_y = 16., columns = 2, rows = 1))]
     // #[asset(path = "player_shadow.png")]
     // pub plane_shadow: Handle<TextureAtlas>,
-    #[asset(path = "background_0.png")]
-    pub background: Handle<Image>,
+    #[asset(path = "campaign.campaign.toml")]
+    pub campaign: Handle<CampaignAsset>,
+
+    #[asset(path = "weapons.weapons.toml")]
+    pub weapons: Handle<WeaponsAsset>,
 
     #[asset(texture_atlas(tile_size_x = 16., tile_size_y = 16., columns = 3, rows = 2))]
     #[asset(path = "coin.png")]
@@ -100,11 +130,38 @@ pub struct Score {
     amount: i64,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameMode {
+    Campaign,
+    /// Procedurally generated waves that never run out
+    Endless,
+}
+
+#[derive(Component)]
+struct MenuButton(GameMode);
+
 #[derive(Component)]
 pub struct UIScore;
 
+#[derive(Component)]
+pub struct UIWeaponLevel;
+
+/// Ammo or heat of the player's weapon
+#[derive(Component)]
+pub struct UIWeaponResource;
+
+/// Entity pool counters, only shown in debug builds
+#[derive(Component)]
+pub struct UIPoolStats;
+
 fn main() {
-    let config = load_config();
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
     App::new()
         .insert_resource(config)
@@ -130,7 +187,7 @@ fn main() {
         )
         .add_loading_state(
             LoadingState::new(GameState::LoadingInGame)
-                .continue_to_state(GameState::InGame)
+                .continue_to_state(GameState::LoadingStage)
                 // .with_dynamic_collections::<StandardDynamicAssetCollection>(vec![
                 //     "dynamic_assets.assets",
                 // ])
@@ -144,20 +201,31 @@ fn main() {
         // .add_plugin(FrameTimeDiagnosticsPlugin::default())
         // ==========================================================
         // Game plugins =============================================
+        .add_plugin(ConfigWatcherPlugin)
         .add_plugin(EventSystemPlugin)
         .add_plugin(InputManagerPlugin::<InputAction>::default())
         // ==========================================================
         // Gameplay plugins =========================================
         .add_plugin(PlayerPlugin)
         .add_plugin(ShootPlugin)
+        .add_plugin(WeaponPlugin)
+        .add_plugin(EnemyFirePlugin)
+        .add_plugin(HomingPlugin)
+        .add_plugin(BeamPlugin)
         .add_plugin(MoveablePlugin)
+        .add_plugin(OffscreenPlugin)
         .add_plugin(PathMovementPlugin) // TODO: this should be in enemy wave spawner?
+        .add_plugin(SteeringPlugin)
         // .add_plugin(EnemyPlugin)
         .add_plugin(AnimatorPlugin)
+        .add_plugin(PoolPlugin)
         .add_plugin(CollisionPlugin)
         .add_plugin(EnemyWaveSpawnerPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(CampaignPlugin)
         // ==========================================================
         .insert_resource(Score { amount: 0 })
+        .insert_resource(GameMode::Campaign)
         // ==========================================================
         .add_enter_system(GameState::MainMenu, setup_main_menu)
         .add_enter_system(GameState::InGame, setup_in_game)
@@ -221,28 +289,8 @@ fn setup_main_menu(mut commands: Commands, config: Res<ConfigData>, menu_assets:
                 });
 
             // Buttons
-            parent
-                .spawn_bundle(ButtonBundle {
-                    style: Style {
-                        size: Size::new(Val::Px(150.), Val::Px(65.)),
-                        margin: UiRect::all(Val::Auto),
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
-                        ..Default::default()
-                    },
-                    color: Color::rgb(0.15, 0.15, 0.15).into(),
-                    ..Default::default()
-                })
-                .with_children(|parent| {
-                    parent.spawn_bundle(TextBundle::from_section(
-                        "Button",
-                        TextStyle {
-                            font: menu_assets.font.clone(),
-                            font_size: 32.,
-                            color: Color::WHITE,
-                        },
-                    ));
-                });
+            spawn_menu_button(parent, &menu_assets.font, "Play", GameMode::Campaign);
+            spawn_menu_button(parent, &menu_assets.font, "Endless", GameMode::Endless);
         })
         .insert(UIScore);
 
@@ -251,13 +299,43 @@ fn setup_main_menu(mut commands: Commands, config: Res<ConfigData>, menu_assets:
     // commands.insert_resource(NextState(GameState::LoadingInGame));
 }
 
+fn spawn_menu_button(parent: &mut ChildBuilder, font: &Handle<Font>, label: &str, mode: GameMode) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.), Val::Px(65.)),
+                margin: UiRect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgb(0.15, 0.15, 0.15).into(),
+            ..Default::default()
+        })
+        .insert(MenuButton(mode))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 32.,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
 fn main_menu_system(
     mut commands: Commands,
-    mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    mut interaction_query: Query<(&Interaction, &MenuButton), (Changed<Interaction>, With<Button>)>,
 ) {
-    for interaction in &mut interaction_query {
+    for (interaction, menu_button) in &mut interaction_query {
         match *interaction {
             Interaction::Clicked => {
+                // New game, nothing is carried over from the previous run
+                commands.insert_resource(menu_button.0);
+                commands.insert_resource(Score { amount: 0 });
+                commands.insert_resource(CampaignProgress::default());
                 commands.insert_resource(NextState(GameState::LoadingInGame));
             }
             Interaction::Hovered => {}
@@ -266,17 +344,23 @@ fn main_menu_system(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn setup_in_game(
     mut commands: Commands,
     core_assets: ResMut<CoreAssets>,
+    stage_assets: Res<StageAssets>,
     config: Res<ConfigData>,
     score: Res<Score>,
-    menu_entities_query: Query<Entity, With<Transform>>,
+    // Player is carried over from the previous stage
+    mut player_query: Query<&mut Transform, With<Player>>,
+    previous_entities_query: Query<Entity, (With<Transform>, Without<Player>, Without<Parent>)>,
+    mut pools: ResMut<EntityPools>,
 ) {
-    println!("unloading menu assets");
-    for entity in menu_entities_query.iter() {
-        commands.entity(entity).despawn();
+    println!("unloading menu and previous stage");
+    for entity in previous_entities_query.iter() {
+        commands.entity(entity).despawn_recursive();
     }
+    pools.clear();
 
     println!("Setting up in-game...");
 
@@ -308,6 +392,74 @@ fn setup_in_game(
         )
         .insert(UIScore);
 
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "LV 1",
+                TextStyle {
+                    font: core_assets.font.clone(),
+                    font_size: 30.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(UIWeaponLevel);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: core_assets.font.clone(),
+                    font_size: 20.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(50.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(UIWeaponResource);
+
+    if cfg!(debug_assertions) {
+        commands
+            .spawn_bundle(
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: core_assets.font.clone(),
+                        font_size: 14.,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(10.),
+                        top: Val::Px(10.),
+                        ..default()
+                    },
+                    ..default()
+                }),
+            )
+            .insert(UIPoolStats);
+    }
+
     // let tower = craete_entity_from_atlas(
     //     &mut commands,
     //     &core_asssets.general,
@@ -319,7 +471,7 @@ fn setup_in_game(
     // Background
     let background = spawn_entity(
         &mut commands,
-        core_assets.background.clone(),
+        stage_assets.background.clone(),
         Vec3::new(0., -50., 0.),
         Anchor::BottomCenter,
     );
@@ -331,6 +483,11 @@ fn setup_in_game(
         ;
 
     // Player
+    if let Ok(mut player_transform) = player_query.get_single_mut() {
+        player_transform.translation = Vec3::new(0., 0., 100.);
+        return;
+    }
+
     let player_entity = spawn_animated_entity(
         &mut commands,
         Vec3::new(0., 0., 100.),
@@ -358,6 +515,30 @@ fn setup_in_game(
         .entity(player_shadow)
         .insert(Transform::from_xyz(-15., -15., -1.));
 
+    let charge_indicator = craete_entity_from_atlas(
+        &mut commands,
+        &core_assets.general,
+        0,
+        Vec3::new(0., 10., 1.),
+    );
+    commands
+        .entity(charge_indicator)
+        .insert(Name::new("ChargeIndicator"))
+        .insert(ChargeIndicator)
+        .insert(Visibility { is_visible: false });
+
+    // Secondary weapon gets its own entity so it can keep its own cooldown
+    let secondary_weapon = commands
+        .spawn_bundle(SpatialBundle::default())
+        .insert(Name::new("SecondaryWeapon"))
+        .insert(SecondaryWeapon)
+        .insert(Shootable::new(
+            config.player.secondary_weapon.clone(),
+            Vec3::new(0., 1., 0.),
+            player_entity,
+        ))
+        .id();
+
     commands
         .entity(player_entity)
         .insert(Name::new(format!("Player_{}", player_entity.id())))
@@ -371,33 +552,41 @@ fn setup_in_game(
             // TODO: calcuate total value from upgrades
             amount: config.player.base_health,
         })
-        .insert(Collider)
-        .insert(Shootable {
-            direction: Vec3::new(0., 1., 0.),
-            source: player_entity,
-            shoot_speed_per_ms: 500, // TODO To be calculated from upgrades
-            time: Stopwatch::new(),
-            is_shooting: false,
-        })
+        .insert(Collider::circle(config.player.hitbox_radius))
+        .insert(CollisionLayers::new(CollisionLayer::Player))
+        .insert(Charge::default())
+        .insert(Shootable::new(
+            config.player.weapon.clone(), // TODO To be changed by upgrades
+            Vec3::new(0., 1., 0.),
+            player_entity,
+        ))
         .insert_bundle(InputManagerBundle::<InputAction> {
             action_state: ActionState::default(),
             input_map: InputMap::new([
                 (KeyCode::Space, InputAction::Shoot),
+                (KeyCode::LShift, InputAction::Shoot_Secondary),
                 (KeyCode::W, InputAction::Move_Up),
                 (KeyCode::S, InputAction::Move_Down),
                 (KeyCode::A, InputAction::Move_Left),
                 (KeyCode::D, InputAction::Move_Right),
             ]),
         })
-        .add_child(player_shadow);
+        .add_child(player_shadow)
+        .add_child(charge_indicator)
+        .add_child(secondary_weapon);
 }
 
-fn setup_game_over(mut commands: Commands, entities_query: Query<Entity, With<Transform>>) {
+fn setup_game_over(
+    mut commands: Commands,
+    entities_query: Query<Entity, With<Transform>>,
+    mut pools: ResMut<EntityPools>,
+) {
     println!("game over");
 
     for entity in entities_query.iter() {
         commands.entity(entity).despawn();
     }
+    pools.clear();
 
     commands.insert_resource(NextState(GameState::LoadingMainMenu));
 }
@@ -1,12 +1,137 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
 use bevy::prelude::*;
 use iyes_loopless::prelude::ConditionSet;
+use serde::Deserialize;
+
+use crate::GameState;
+
+/// Side to side motion on top of the direction of travel
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Oscillation {
+    Sine { amplitude: f32, frequency: f32 },
+    ZigZag { amplitude: f32, frequency: f32 },
+}
+
+impl Oscillation {
+    /// Sideways offset after `seconds`
+    fn offset(&self, seconds: f32) -> f32 {
+        match *self {
+            Oscillation::Sine {
+                amplitude,
+                frequency,
+            } => amplitude * (seconds * frequency * TAU).sin(),
+            Oscillation::ZigZag {
+                amplitude,
+                frequency,
+            } => {
+                // Triangle wave starting at 0 like the sine
+                let phase = (seconds * frequency + 0.25).fract();
+                amplitude * (1. - 4. * (phase - 0.5).abs())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RotationMode {
+    /// Leave the rotation alone
+    Keep,
+    /// Turn the sprite, which faces +Y, along the actual velocity
+    FaceVelocity,
+    /// Spin at the given radians per second
+    Spin(f32),
+}
 
-use crate::{utils::load_config::ConfigData, GameState};
 #[derive(Component)]
 pub struct Moveable {
     pub direction: Vec3,
     pub speed: f32,
-    pub auto_destroy: bool,
+    /// Units per second squared along `direction`, negative slows down
+    pub acceleration: f32,
+    /// Fraction of the speed lost per second
+    pub drag: f32,
+    pub max_speed: f32,
+    /// Radians per second `direction` turns, curves the trajectory
+    pub angular_velocity: f32,
+    pub oscillation: Option<Oscillation>,
+    pub rotation_mode: RotationMode,
+    elapsed: f32,
+}
+
+impl Moveable {
+    pub fn new(direction: Vec3, speed: f32) -> Self {
+        Self {
+            direction,
+            speed,
+            acceleration: 0.,
+            drag: 0.,
+            max_speed: f32::INFINITY,
+            angular_velocity: 0.,
+            oscillation: None,
+            rotation_mode: RotationMode::FaceVelocity,
+            elapsed: 0.,
+        }
+    }
+
+    pub fn with_acceleration(mut self, acceleration: f32) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    pub fn with_max_speed(mut self, max_speed: f32) -> Self {
+        self.max_speed = max_speed;
+        self
+    }
+
+    pub fn with_angular_velocity(mut self, angular_velocity: f32) -> Self {
+        self.angular_velocity = angular_velocity;
+        self
+    }
+
+    pub fn with_oscillation(mut self, oscillation: Oscillation) -> Self {
+        self.oscillation = Some(oscillation);
+        self
+    }
+
+    pub fn with_rotation_mode(mut self, rotation_mode: RotationMode) -> Self {
+        self.rotation_mode = rotation_mode;
+        self
+    }
+
+    /// Advances the speed, direction and oscillation by `delta_seconds` and
+    /// returns how far the entity moves
+    fn step(&mut self, delta_seconds: f32) -> Vec2 {
+        // Speed
+        let speed = self.speed + self.acceleration * delta_seconds;
+        let speed = speed * (1. - self.drag * delta_seconds).max(0.);
+        self.speed = speed.clamp(0., self.max_speed);
+
+        // Direction
+        let direction = self.direction.truncate().normalize_or_zero();
+        let direction = Mat2::from_angle(self.angular_velocity * delta_seconds) * direction;
+        self.direction = direction.extend(self.direction.z);
+
+        let mut step = direction * self.speed * delta_seconds;
+
+        // Oscillation moves by the change of the sideways offset this frame
+        let previous_elapsed = self.elapsed;
+        self.elapsed += delta_seconds;
+        if let Some(oscillation) = self.oscillation {
+            let sideways = Vec2::new(direction.y, -direction.x);
+            let offset_change =
+                oscillation.offset(self.elapsed) - oscillation.offset(previous_elapsed);
+            step += sideways * offset_change;
+        }
+
+        return step;
+    }
 }
 
 pub struct MoveablePlugin;
@@ -22,29 +147,109 @@ impl Plugin for MoveablePlugin {
     }
 }
 
-fn update_moveables(
-    mut commands: Commands,
-    mut moveable_query: Query<(Entity, &mut Transform, &Moveable), With<Moveable>>,
-    time: Res<Time>,
-    config: Res<ConfigData>,
-) {
-    for (entity, mut moveable_transform, moveable) in moveable_query.iter_mut() {
-        moveable_transform.translation += Vec3::new(
-            moveable.direction.x * moveable.speed * time.delta_seconds(),
-            moveable.direction.y * moveable.speed * time.delta_seconds(),
-            0.,
-        );
+fn update_moveables(mut moveable_query: Query<(&mut Transform, &mut Moveable)>, time: Res<Time>) {
+    let delta_seconds = time.delta_seconds();
+    if delta_seconds <= 0. {
+        return;
+    }
+
+    for (mut moveable_transform, mut moveable) in moveable_query.iter_mut() {
+        let step = moveable.step(delta_seconds);
+        moveable_transform.translation += step.extend(0.);
+
+        match moveable.rotation_mode {
+            RotationMode::Keep => {}
+            RotationMode::FaceVelocity => {
+                if step != Vec2::ZERO {
+                    moveable_transform.rotation =
+                        Quat::from_rotation_z(step.y.atan2(step.x) - FRAC_PI_2);
+                }
+            }
+            RotationMode::Spin(radians_per_second) => {
+                moveable_transform.rotation *=
+                    Quat::from_rotation_z(radians_per_second * delta_seconds);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acceleration_stops_at_max_speed() {
+        let mut moveable = Moveable::new(Vec3::Y, 10.)
+            .with_acceleration(20.)
+            .with_max_speed(35.);
+
+        moveable.step(1.);
+        assert_eq!(moveable.speed, 30.);
+        moveable.step(1.);
+        assert_eq!(moveable.speed, 35.);
+    }
+
+    #[test]
+    fn deceleration_stops_at_zero() {
+        let mut moveable = Moveable::new(Vec3::Y, 10.).with_acceleration(-20.);
+
+        let step = moveable.step(1.);
+        assert_eq!(moveable.speed, 0.);
+        assert_eq!(step, Vec2::ZERO);
+    }
+
+    #[test]
+    fn drag_loses_a_fraction_of_the_speed_per_second() {
+        let mut moveable = Moveable::new(Vec3::Y, 100.).with_drag(0.5);
+
+        let step = moveable.step(0.5);
+        assert_eq!(moveable.speed, 75.);
+        assert!(step.abs_diff_eq(Vec2::new(0., 37.5), 1e-4));
+
+        // Never reverses, however long the frame
+        moveable.step(10.);
+        assert_eq!(moveable.speed, 0.);
+    }
+
+    #[test]
+    fn angular_velocity_turns_the_direction() {
+        let mut moveable = Moveable::new(Vec3::X, 10.).with_angular_velocity(FRAC_PI_2);
+
+        moveable.step(1.);
+        assert!(moveable.direction.abs_diff_eq(Vec3::Y, 1e-4));
+    }
+
+    #[test]
+    fn oscillation_swings_sideways_and_comes_back() {
+        for oscillation in [
+            Oscillation::Sine {
+                amplitude: 8.,
+                frequency: 1.,
+            },
+            Oscillation::ZigZag {
+                amplitude: 8.,
+                frequency: 1.,
+            },
+        ] {
+            let mut moveable = Moveable::new(Vec3::Y, 10.).with_oscillation(oscillation);
 
-        let angle = (moveable.direction - moveable_transform.translation)
-            .angle_between(moveable_transform.translation);
-        moveable_transform.rotation = Quat::from_rotation_z(angle);
+            // A quarter period reaches the full amplitude to the right
+            let mut position = moveable.step(0.25);
+            assert!(
+                position.abs_diff_eq(Vec2::new(8., 2.5), 1e-4),
+                "{:?}",
+                oscillation
+            );
 
-        if moveable.auto_destroy {
-            if moveable_transform.translation.y > config.general.screen_height
-            //  || moveable_transform.translation.y < 0.
-            {
-                commands.entity(entity).despawn();
+            // A full period ends back on the line of travel
+            for _ in 0..3 {
+                position += moveable.step(0.25);
             }
+            assert!(
+                position.abs_diff_eq(Vec2::new(0., 10.), 1e-4),
+                "{:?}",
+                oscillation
+            );
         }
     }
 }
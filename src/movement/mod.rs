@@ -1 +1,3 @@
 pub mod path_movement;
+pub mod spline;
+pub mod steering;
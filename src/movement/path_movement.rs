@@ -1,13 +1,100 @@
+use std::f32::consts::FRAC_PI_2;
+
 use bevy::prelude::*;
+use bevy_pixel_camera::PixelProjection;
 use iyes_loopless::prelude::ConditionSet;
+use serde::Deserialize;
+
+use super::spline::SplinePath;
+use crate::{
+    event_system::EnemyEscaped,
+    offscreen::visible_area,
+    utils::{config_watcher::ConfigChanged, load_config::ConfigData},
+    GameState,
+};
+
+/// What happens once the end of the path is reached
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathEnd {
+    /// Despawn and count as escaped
+    #[default]
+    Despawn,
+    /// Start over from the first point
+    Loop,
+    /// Go back along the path, then forward again
+    PingPong,
+    /// Stay at the last point
+    Hold,
+    /// Keep flying along the last tangent until off screen, then despawn
+    Exit,
+}
 
-use crate::{spawners::enemy_wave_spawner::WaveData, GameState};
+/// Distance past the visible area before an exiting entity is removed
+const EXIT_MARGIN: f32 = 20.;
 
 #[derive(Component)]
 pub struct PathMoveable {
-    pub next_path_index: usize,
-    pub move_positions: Vec<Vec2>,
+    pub path: SplinePath,
+    /// Distance travelled along `path`
+    pub distance: f32,
+    pub end: PathEnd,
+    /// 1 forward along the path, -1 on the way back of a ping-pong
+    direction: f32,
+    /// Units per second along the path
     pub movement_speed: f32,
+    /// Scales `enemies.movement_speed` from the config, set per wave
+    pub speed_multiplier: f32,
+    /// Point on the path, the entity itself sits at `position + offset`
+    pub position: Vec2,
+    heading: Vec2,
+    /// Formation offset, +Y is the direction of travel
+    pub offset: Vec2,
+    /// Rotate `offset` with the heading so formations keep their shape in turns
+    pub rotate_offset: bool,
+}
+
+impl PathMoveable {
+    pub fn new(path: SplinePath, movement_speed: f32, speed_multiplier: f32) -> Self {
+        let (position, heading) = path.sample(0.);
+
+        Self {
+            path,
+            distance: 0.,
+            end: PathEnd::Despawn,
+            direction: 1.,
+            movement_speed: movement_speed * speed_multiplier,
+            speed_multiplier,
+            position,
+            heading,
+            offset: Vec2::ZERO,
+            rotate_offset: false,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2, rotate_offset: bool) -> Self {
+        self.offset = offset;
+        self.rotate_offset = rotate_offset;
+        self
+    }
+
+    pub fn with_end(mut self, end: PathEnd) -> Self {
+        self.end = end;
+        self
+    }
+
+    pub fn current_position(&self) -> Vec2 {
+        return self.position + self.world_offset();
+    }
+
+    fn world_offset(&self) -> Vec2 {
+        if !self.rotate_offset || self.heading == Vec2::ZERO {
+            return self.offset;
+        }
+
+        let right = Vec2::new(self.heading.y, -self.heading.x);
+        return right * self.offset.x + self.heading * self.offset.y;
+    }
 }
 
 pub struct PathMovementPlugin;
@@ -18,6 +105,7 @@ impl Plugin for PathMovementPlugin {
             ConditionSet::new()
                 .run_in_state(GameState::InGame)
                 .with_system(movement)
+                .with_system(apply_config_changes)
                 .into(),
         );
     }
@@ -25,27 +113,89 @@ impl Plugin for PathMovementPlugin {
 
 fn movement(
     mut commands: Commands,
-    mut path_moveable_query: Query<(&mut Transform, &mut PathMoveable, Entity), With<PathMoveable>>,
+    mut path_moveable_query: Query<(&mut Transform, &mut PathMoveable, Entity)>,
+    camera_query: Query<(&Transform, &PixelProjection), Without<PathMoveable>>,
+    mut escaped_events: EventWriter<EnemyEscaped>,
+    time: Res<Time>,
 ) {
+    let visible_area = camera_query
+        .get_single()
+        .map(|(transform, projection)| visible_area(transform, projection))
+        .ok();
+
     for (mut transform, mut path_moveable, entity) in path_moveable_query.iter_mut() {
         // Movement
-        let target = path_moveable.move_positions[path_moveable.next_path_index].extend(100.);
-        let difference = target - transform.translation;
-        let dot = Vec3::dot(difference, difference);
-        if dot < 1. + path_moveable.movement_speed
-            && path_moveable.next_path_index < path_moveable.move_positions.len()
-        {
-            path_moveable.next_path_index += 1;
-        } else {
-            transform.translation += difference.normalize() * path_moveable.movement_speed;
+        let step = path_moveable.movement_speed * time.delta_seconds();
+        path_moveable.distance += step * path_moveable.direction;
+
+        let length = path_moveable.path.length();
+        let mut past_end = 0.;
+        match path_moveable.end {
+            PathEnd::Despawn => {
+                if path_moveable.distance >= length {
+                    commands.entity(entity).despawn();
+                    escaped_events.send(EnemyEscaped { entity });
+                }
+            }
+            PathEnd::Loop => {
+                if length > 0. {
+                    path_moveable.distance = path_moveable.distance.rem_euclid(length);
+                }
+            }
+            PathEnd::PingPong => {
+                if path_moveable.distance >= length {
+                    path_moveable.distance = 2. * length - path_moveable.distance;
+                    path_moveable.direction = -1.;
+                } else if path_moveable.distance <= 0. {
+                    path_moveable.distance = -path_moveable.distance;
+                    path_moveable.direction = 1.;
+                }
+                path_moveable.distance = path_moveable.distance.clamp(0., length);
+            }
+            PathEnd::Hold => path_moveable.distance = path_moveable.distance.min(length),
+            PathEnd::Exit => past_end = (path_moveable.distance - length).max(0.),
+        }
+
+        let (position, tangent) = path_moveable.path.sample(path_moveable.distance);
+        if tangent != Vec2::ZERO {
+            path_moveable.heading = tangent * path_moveable.direction;
         }
+        path_moveable.position = position + path_moveable.heading * past_end;
+
+        transform.translation = path_moveable
+            .current_position()
+            .extend(transform.translation.z);
 
-        if path_moveable.next_path_index >= path_moveable.move_positions.len() {
-            commands.entity(entity).despawn();
+        if past_end > 0. {
+            if let Some((min, max)) = visible_area {
+                let position = transform.translation.truncate();
+                let margin = Vec2::splat(EXIT_MARGIN);
+                if position.cmplt(min - margin).any() || position.cmpgt(max + margin).any() {
+                    commands.entity(entity).despawn();
+                    escaped_events.send(EnemyEscaped { entity });
+                }
+            }
         }
 
-        // Rotation
-        let angle = (target - transform.translation).angle_between(transform.translation);
-        transform.rotation = Quat::from_rotation_z(angle);
+        // Rotation, sprites face +Y
+        let heading = path_moveable.heading;
+        if heading != Vec2::ZERO {
+            transform.rotation = Quat::from_rotation_z(heading.y.atan2(heading.x) - FRAC_PI_2);
+        }
+    }
+}
+
+fn apply_config_changes(
+    mut config_changed_events: EventReader<ConfigChanged>,
+    mut path_moveable_query: Query<&mut PathMoveable>,
+    config: Res<ConfigData>,
+) {
+    if config_changed_events.iter().last().is_none() {
+        return;
+    }
+
+    for mut path_moveable in path_moveable_query.iter_mut() {
+        path_moveable.movement_speed =
+            config.enemies.movement_speed * path_moveable.speed_multiplier;
     }
 }
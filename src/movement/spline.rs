@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Samples taken per curve segment when building the arc length table
+const SAMPLES_PER_SEGMENT: usize = 16;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathKind {
+    /// Straight lines between the points
+    #[default]
+    Linear,
+    /// Smooth curve through every point
+    CatmullRom,
+    /// Cubic Bezier segments, `start, control, control, end, control, control, end...`
+    Bezier,
+}
+
+impl PathKind {
+    /// Number of curve segments the control points make, `None` if the
+    /// point count does not fit the kind
+    pub fn segment_count(&self, point_count: usize) -> Option<usize> {
+        match self {
+            PathKind::Linear | PathKind::CatmullRom if point_count >= 2 => Some(point_count - 1),
+            PathKind::Bezier if point_count >= 4 && (point_count - 1) % 3 == 0 => {
+                Some((point_count - 1) / 3)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Speed curve within one segment of a path
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    fn apply(&self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2. - t),
+            Easing::EaseInOut => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PathSegment {
+    start_distance: f32,
+    end_distance: f32,
+    easing: Easing,
+}
+
+/// Path sampled by arc length, so moving a fixed distance along it takes the
+/// same time no matter how the control points are spaced
+#[derive(Clone)]
+pub struct SplinePath {
+    samples: Vec<Vec2>,
+    /// Distance along the path of every sample
+    distances: Vec<f32>,
+    segments: Vec<PathSegment>,
+}
+
+impl SplinePath {
+    /// `easings` are per segment, missing ones are linear. Control points
+    /// must fit `kind`, see `PathKind::segment_count`.
+    pub fn new(kind: PathKind, points: &[Vec2], easings: &[Easing]) -> Self {
+        let segment_count = kind.segment_count(points.len()).unwrap_or(0);
+
+        let mut samples = vec![points.first().copied().unwrap_or(Vec2::ZERO)];
+        let mut distances = vec![0.];
+        let mut segments = Vec::with_capacity(segment_count);
+
+        for segment in 0..segment_count {
+            let start_distance = *distances.last().unwrap();
+
+            for step in 1..=SAMPLES_PER_SEGMENT {
+                let t = step as f32 / SAMPLES_PER_SEGMENT as f32;
+                let point = match kind {
+                    PathKind::Linear => points[segment].lerp(points[segment + 1], t),
+                    PathKind::CatmullRom => catmull_rom(points, segment, t),
+                    PathKind::Bezier => bezier(&points[segment * 3..segment * 3 + 4], t),
+                };
+
+                let distance = distances.last().unwrap() + samples.last().unwrap().distance(point);
+                samples.push(point);
+                distances.push(distance);
+            }
+
+            segments.push(PathSegment {
+                start_distance,
+                end_distance: *distances.last().unwrap(),
+                easing: easings.get(segment).copied().unwrap_or_default(),
+            });
+        }
+
+        Self {
+            samples,
+            distances,
+            segments,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        return *self.distances.last().unwrap();
+    }
+
+    /// Position and unit tangent `distance` units along the path
+    pub fn sample(&self, distance: f32) -> (Vec2, Vec2) {
+        if self.samples.len() < 2 {
+            return (self.samples[0], Vec2::ZERO);
+        }
+
+        let distance = self.eased_distance(distance.clamp(0., self.length()));
+
+        // First sample past the distance, the point lies between it and the one before
+        let next = self
+            .distances
+            .partition_point(|&sample_distance| sample_distance < distance)
+            .clamp(1, self.samples.len() - 1);
+
+        let (from, to) = (self.samples[next - 1], self.samples[next]);
+        let span = self.distances[next] - self.distances[next - 1];
+        let t = if span > 0. {
+            (distance - self.distances[next - 1]) / span
+        } else {
+            0.
+        };
+
+        return (from.lerp(to, t), (to - from).normalize_or_zero());
+    }
+
+    /// Remaps a distance with the easing of the segment it falls in
+    fn eased_distance(&self, distance: f32) -> f32 {
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| distance <= segment.end_distance);
+
+        return match segment {
+            Some(segment) if segment.end_distance > segment.start_distance => {
+                let length = segment.end_distance - segment.start_distance;
+                let t = (distance - segment.start_distance) / length;
+                segment.start_distance + segment.easing.apply(t) * length
+            }
+            _ => distance,
+        };
+    }
+}
+
+fn catmull_rom(points: &[Vec2], segment: usize, t: f32) -> Vec2 {
+    // End points are repeated so the curve still passes through them
+    let p0 = points[segment.saturating_sub(1)];
+    let p1 = points[segment];
+    let p2 = points[segment + 1];
+    let p3 = points[(segment + 2).min(points.len() - 1)];
+
+    let t2 = t * t;
+    let t3 = t2 * t;
+    return 0.5
+        * (2. * p1
+            + (p2 - p0) * t
+            + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2
+            + (3. * p1 - p0 - 3. * p2 + p3) * t3);
+}
+
+fn bezier(points: &[Vec2], t: f32) -> Vec2 {
+    let u = 1. - t;
+    return points[0] * u * u * u
+        + points[1] * 3. * u * u * t
+        + points[2] * 3. * u * t * t
+        + points[3] * t * t * t;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn curve_points() -> Vec<Vec2> {
+        return vec![
+            Vec2::new(0., 0.),
+            Vec2::new(10., 40.),
+            Vec2::new(60., 50.),
+            Vec2::new(100., -20.),
+        ];
+    }
+
+    #[test]
+    fn sample_starts_and_ends_at_the_end_points() {
+        let points = curve_points();
+        for kind in [PathKind::Linear, PathKind::CatmullRom, PathKind::Bezier] {
+            let path = SplinePath::new(kind, &points, &[]);
+
+            let (start, _) = path.sample(0.);
+            let (end, _) = path.sample(path.length());
+            assert!(start.abs_diff_eq(points[0], EPSILON), "{:?}", kind);
+            assert!(end.abs_diff_eq(points[3], EPSILON), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn sample_clamps_outside_the_path() {
+        let path = SplinePath::new(PathKind::CatmullRom, &curve_points(), &[]);
+
+        assert_eq!(path.sample(-5.).0, path.sample(0.).0);
+        assert_eq!(
+            path.sample(path.length() + 5.).0,
+            path.sample(path.length()).0
+        );
+    }
+
+    #[test]
+    fn linear_samples_are_evenly_spaced() {
+        // Segments of very different lengths
+        let points = [Vec2::new(0., 0.), Vec2::new(10., 0.), Vec2::new(100., 0.)];
+        let path = SplinePath::new(PathKind::Linear, &points, &[]);
+
+        assert!((path.length() - 100.).abs() < EPSILON);
+        for step in 0..=10 {
+            let distance = step as f32 * 10.;
+            let (position, tangent) = path.sample(distance);
+            assert!(position.abs_diff_eq(Vec2::new(distance, 0.), EPSILON));
+            assert!(tangent.abs_diff_eq(Vec2::X, EPSILON));
+        }
+    }
+
+    #[test]
+    fn curve_samples_are_evenly_spaced() {
+        for kind in [PathKind::CatmullRom, PathKind::Bezier] {
+            let path = SplinePath::new(kind, &curve_points(), &[]);
+            let step = path.length() / 20.;
+
+            for index in 0..20 {
+                let (from, _) = path.sample(index as f32 * step);
+                let (to, _) = path.sample((index + 1) as f32 * step);
+                let spacing = from.distance(to);
+                assert!(
+                    (spacing - step).abs() < step * 0.05,
+                    "{:?} step {} is {} long, expected {}",
+                    kind,
+                    index,
+                    spacing,
+                    step
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn easing_remaps_the_distance_within_a_segment() {
+        let points = [Vec2::new(0., 0.), Vec2::new(100., 0.)];
+
+        let ease_in = SplinePath::new(PathKind::Linear, &points, &[Easing::EaseIn]);
+        assert!(ease_in
+            .sample(50.)
+            .0
+            .abs_diff_eq(Vec2::new(25., 0.), EPSILON));
+
+        let ease_out = SplinePath::new(PathKind::Linear, &points, &[Easing::EaseOut]);
+        assert!(ease_out
+            .sample(50.)
+            .0
+            .abs_diff_eq(Vec2::new(75., 0.), EPSILON));
+
+        // End points stay put
+        assert!(ease_in.sample(0.).0.abs_diff_eq(points[0], EPSILON));
+        assert!(ease_in.sample(100.).0.abs_diff_eq(points[1], EPSILON));
+    }
+
+    #[test]
+    fn segment_count_rejects_bad_point_counts() {
+        assert_eq!(PathKind::Linear.segment_count(1), None);
+        assert_eq!(PathKind::Linear.segment_count(3), Some(2));
+        assert_eq!(PathKind::CatmullRom.segment_count(0), None);
+        assert_eq!(PathKind::CatmullRom.segment_count(2), Some(1));
+        assert_eq!(PathKind::Bezier.segment_count(3), None);
+        assert_eq!(PathKind::Bezier.segment_count(5), None);
+        assert_eq!(PathKind::Bezier.segment_count(4), Some(1));
+        assert_eq!(PathKind::Bezier.segment_count(7), Some(2));
+    }
+
+    #[test]
+    fn bad_point_counts_make_an_empty_path() {
+        let points = [
+            Vec2::new(5., 5.),
+            Vec2::new(10., 0.),
+            Vec2::new(20., 0.),
+            Vec2::new(30., 0.),
+            Vec2::new(40., 0.),
+        ];
+        let path = SplinePath::new(PathKind::Bezier, &points, &[]);
+
+        assert_eq!(path.length(), 0.);
+        assert_eq!(path.sample(10.), (Vec2::new(5., 5.), Vec2::ZERO));
+    }
+}
@@ -0,0 +1,357 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::ConditionSet;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{event_system::EnemyEscaped, player::Player, GameState};
+
+// Data =========================================================
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SteeringBehaviour {
+    /// Head straight for the player
+    Seek,
+    /// Turn towards the player at most `turn_rate` radians per second
+    Homing { turn_rate: f32 },
+    /// Seek until within `lock_distance` of the player, then dive in a
+    /// straight line without turning again
+    Kamikaze { lock_distance: f32 },
+    /// Move away from the player while closer than `radius`
+    Flee { radius: f32 },
+    /// Drift around, `jitter` is how many radians the heading can change per second
+    Wander { jitter: f32 },
+    /// Circle `center`, or the player when there is none
+    Orbit {
+        #[serde(default)]
+        center: Option<[f32; 2]>,
+        radius: f32,
+        #[serde(default)]
+        clockwise: bool,
+    },
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct WeightedBehaviour {
+    pub behaviour: SteeringBehaviour,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.
+}
+
+/// Steering as written in level files
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SteeringDefinition {
+    pub behaviours: Vec<WeightedBehaviour>,
+    /// Units per second
+    pub max_speed: f32,
+    /// Units per second squared
+    pub max_acceleration: f32,
+    /// Seconds before the entity gives up and leaves, counted as escaped
+    #[serde(default = "default_lifetime")]
+    pub lifetime: f32,
+}
+
+fn default_lifetime() -> f32 {
+    15.
+}
+
+impl SteeringDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.behaviours.is_empty() {
+            return Err("steering needs at least one behaviour".to_string());
+        }
+        if self.max_speed.is_nan() || self.max_speed <= 0. {
+            return Err("steering.max_speed must be greater than 0".to_string());
+        }
+        if self.max_acceleration.is_nan() || self.max_acceleration <= 0. {
+            return Err("steering.max_acceleration must be greater than 0".to_string());
+        }
+        if self.lifetime.is_nan() || self.lifetime <= 0. {
+            return Err("steering.lifetime must be greater than 0".to_string());
+        }
+        if self
+            .behaviours
+            .iter()
+            .any(|weighted| weighted.weight.is_nan() || weighted.weight < 0.)
+        {
+            return Err("steering behaviour weights must be 0 or greater".to_string());
+        }
+        return Ok(());
+    }
+}
+// =============================================================
+
+/// Moves the entity with weighted steering behaviours instead of a path
+#[derive(Component)]
+pub struct Steering {
+    pub behaviours: Vec<WeightedBehaviour>,
+    pub max_speed: f32,
+    pub max_acceleration: f32,
+    pub velocity: Vec2,
+    pub lifetime: Timer,
+    /// Direction the wander behaviour is drifting towards
+    wander_angle: f32,
+    /// Dive direction once a kamikaze has locked on
+    locked_direction: Option<Vec2>,
+}
+
+impl Steering {
+    pub fn new(definition: &SteeringDefinition, speed_multiplier: f32, heading: Vec2) -> Self {
+        let max_speed = definition.max_speed * speed_multiplier;
+        let heading = heading.try_normalize().unwrap_or(Vec2::NEG_Y);
+
+        Self {
+            behaviours: definition.behaviours.clone(),
+            max_speed,
+            max_acceleration: definition.max_acceleration * speed_multiplier,
+            velocity: heading * max_speed,
+            lifetime: Timer::from_seconds(definition.lifetime, false),
+            wander_angle: heading.y.atan2(heading.x),
+            locked_direction: None,
+        }
+    }
+
+    fn heading(&self) -> Vec2 {
+        return self.velocity.try_normalize().unwrap_or(Vec2::NEG_Y);
+    }
+
+    /// Velocity of all the behaviours blended by weight
+    fn blended_velocity(
+        &mut self,
+        position: Vec2,
+        player: Option<Vec2>,
+        delta_seconds: f32,
+    ) -> Vec2 {
+        let mut desired = Vec2::ZERO;
+        let mut total_weight = 0.;
+        let behaviours = self.behaviours.clone();
+        for weighted in behaviours.iter() {
+            desired += self.desired_velocity(&weighted.behaviour, position, player, delta_seconds)
+                * weighted.weight;
+            total_weight += weighted.weight;
+        }
+        if total_weight > 0. {
+            desired /= total_weight;
+        }
+        return desired;
+    }
+
+    /// Steers towards `desired` within the acceleration and speed limits
+    fn accelerate(&mut self, desired: Vec2, delta_seconds: f32) {
+        let max_change = self.max_acceleration * delta_seconds;
+        let steering_force = (desired - self.velocity).clamp_length_max(max_change);
+        self.velocity = (self.velocity + steering_force).clamp_length_max(self.max_speed);
+    }
+
+    /// Velocity the behaviour would like to have this frame
+    fn desired_velocity(
+        &mut self,
+        behaviour: &SteeringBehaviour,
+        position: Vec2,
+        player: Option<Vec2>,
+        delta_seconds: f32,
+    ) -> Vec2 {
+        let to_player = player.map(|player| player - position);
+
+        return match *behaviour {
+            SteeringBehaviour::Seek => match to_player {
+                Some(to_player) => to_player.normalize_or_zero() * self.max_speed,
+                None => self.velocity,
+            },
+            SteeringBehaviour::Homing { turn_rate } => {
+                let heading = self.heading();
+                let to_player = match to_player.and_then(|to_player| to_player.try_normalize()) {
+                    Some(to_player) => to_player,
+                    None => return heading * self.max_speed,
+                };
+
+                let angle = heading.angle_between(to_player);
+                let max_turn = turn_rate * delta_seconds;
+                let turn = angle.clamp(-max_turn, max_turn);
+                Mat2::from_angle(turn) * heading * self.max_speed
+            }
+            SteeringBehaviour::Kamikaze { lock_distance } => {
+                if self.locked_direction.is_none() {
+                    if let Some(to_player) = to_player {
+                        if to_player.length() <= lock_distance {
+                            self.locked_direction = Some(to_player.normalize_or_zero());
+                        } else {
+                            return to_player.normalize_or_zero() * self.max_speed;
+                        }
+                    }
+                }
+
+                self.locked_direction.unwrap_or_else(|| self.heading()) * self.max_speed
+            }
+            SteeringBehaviour::Flee { radius } => match to_player {
+                Some(to_player) if to_player.length() < radius => {
+                    -to_player.normalize_or_zero() * self.max_speed
+                }
+                _ => self.velocity,
+            },
+            SteeringBehaviour::Wander { jitter } => {
+                self.wander_angle +=
+                    rand::thread_rng().gen_range(-1.0..=1.0) * jitter * delta_seconds;
+                self.wander_angle %= TAU;
+                Mat2::from_angle(self.wander_angle) * Vec2::X * self.max_speed
+            }
+            SteeringBehaviour::Orbit {
+                center,
+                radius,
+                clockwise,
+            } => {
+                let center = match center.map(Vec2::from).or(player) {
+                    Some(center) => center,
+                    None => return self.velocity,
+                };
+
+                let from_center = position - center;
+                let outward = from_center.try_normalize().unwrap_or(Vec2::X);
+                let tangent = if clockwise {
+                    Vec2::new(outward.y, -outward.x)
+                } else {
+                    Vec2::new(-outward.y, outward.x)
+                };
+                // Pull back onto the circle while going around it
+                let correction = outward * (radius - from_center.length()) / radius.max(1.);
+                (tangent + correction).normalize_or_zero() * self.max_speed
+            }
+        };
+    }
+}
+
+pub struct SteeringPlugin;
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::InGame)
+                .with_system(steer)
+                .into(),
+        );
+    }
+}
+
+fn steer(
+    mut commands: Commands,
+    mut steering_query: Query<(Entity, &mut Transform, &mut Steering), Without<Player>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut escaped_events: EventWriter<EnemyEscaped>,
+    time: Res<Time>,
+) {
+    let player = player_query
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
+    let delta_seconds = time.delta_seconds();
+
+    for (entity, mut transform, mut steering) in steering_query.iter_mut() {
+        if steering.lifetime.tick(time.delta()).just_finished() {
+            commands.entity(entity).despawn_recursive();
+            escaped_events.send(EnemyEscaped { entity });
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let desired = steering.blended_velocity(position, player, delta_seconds);
+        steering.accelerate(desired, delta_seconds);
+
+        transform.translation += steering.velocity.extend(0.) * delta_seconds;
+
+        // Sprites face +Y
+        if steering.velocity != Vec2::ZERO {
+            let velocity = steering.velocity;
+            transform.rotation = Quat::from_rotation_z(velocity.y.atan2(velocity.x) - FRAC_PI_2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steering(behaviours: Vec<(SteeringBehaviour, f32)>) -> Steering {
+        let definition = SteeringDefinition {
+            behaviours: behaviours
+                .into_iter()
+                .map(|(behaviour, weight)| WeightedBehaviour { behaviour, weight })
+                .collect(),
+            max_speed: 100.,
+            max_acceleration: 50.,
+            lifetime: 15.,
+        };
+        return Steering::new(&definition, 1., Vec2::NEG_Y);
+    }
+
+    #[test]
+    fn seek_heads_for_the_player_at_max_speed() {
+        let mut steering = steering(vec![(SteeringBehaviour::Seek, 1.)]);
+        let desired = steering.blended_velocity(Vec2::ZERO, Some(Vec2::new(30., 0.)), 0.1);
+        assert!(desired.abs_diff_eq(Vec2::new(100., 0.), 1e-4));
+    }
+
+    #[test]
+    fn flee_only_inside_its_radius() {
+        let mut steering = steering(vec![(SteeringBehaviour::Flee { radius: 50. }, 1.)]);
+
+        let desired = steering.blended_velocity(Vec2::ZERO, Some(Vec2::new(30., 0.)), 0.1);
+        assert!(desired.abs_diff_eq(Vec2::new(-100., 0.), 1e-4));
+
+        // Keeps the current velocity once far enough
+        let desired = steering.blended_velocity(Vec2::ZERO, Some(Vec2::new(80., 0.)), 0.1);
+        assert!(desired.abs_diff_eq(Vec2::new(0., -100.), 1e-4));
+    }
+
+    #[test]
+    fn behaviours_are_blended_by_weight() {
+        let mut steering = steering(vec![
+            (SteeringBehaviour::Seek, 3.),
+            (SteeringBehaviour::Flee { radius: 50. }, 1.),
+        ]);
+        let desired = steering.blended_velocity(Vec2::ZERO, Some(Vec2::new(30., 0.)), 0.1);
+        assert!(desired.abs_diff_eq(Vec2::new(50., 0.), 1e-4));
+    }
+
+    #[test]
+    fn zero_weights_want_to_stop() {
+        let mut steering = steering(vec![(SteeringBehaviour::Seek, 0.)]);
+        let desired = steering.blended_velocity(Vec2::ZERO, Some(Vec2::new(30., 0.)), 0.1);
+        assert_eq!(desired, Vec2::ZERO);
+    }
+
+    #[test]
+    fn kamikaze_keeps_its_dive_once_locked() {
+        let mut steering = steering(vec![(
+            SteeringBehaviour::Kamikaze { lock_distance: 50. },
+            1.,
+        )]);
+
+        let desired = steering.blended_velocity(Vec2::ZERO, Some(Vec2::new(0., 30.)), 0.1);
+        assert!(desired.abs_diff_eq(Vec2::new(0., 100.), 1e-4));
+
+        // The player dodging doesn't turn it anymore
+        let desired = steering.blended_velocity(Vec2::ZERO, Some(Vec2::new(30., 0.)), 0.1);
+        assert!(desired.abs_diff_eq(Vec2::new(0., 100.), 1e-4));
+    }
+
+    #[test]
+    fn acceleration_is_clamped() {
+        let mut steering = steering(vec![(SteeringBehaviour::Seek, 1.)]);
+        steering.accelerate(Vec2::ZERO, 0.5);
+        // At most 50 units per second squared for half a second
+        assert!(steering.velocity.abs_diff_eq(Vec2::new(0., -75.), 1e-4));
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut steering = steering(vec![(SteeringBehaviour::Seek, 1.)]);
+        steering.accelerate(Vec2::new(0., -500.), 10.);
+        assert!((steering.velocity.length() - 100.).abs() < 1e-4);
+    }
+}
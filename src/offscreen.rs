@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use bevy_pixel_camera::PixelProjection;
+use iyes_loopless::prelude::ConditionSet;
+
+use crate::{
+    enemy::Enemy,
+    event_system::EnemyEscaped,
+    pool::{despawn_or_release, EntityPools, Pooled},
+    GameState,
+};
+
+/// Despawns the entity once it is more than `margin` outside the camera view
+#[derive(Component)]
+pub struct DespawnOffscreen {
+    pub margin: f32,
+}
+
+impl DespawnOffscreen {
+    pub fn new(margin: f32) -> Self {
+        Self { margin }
+    }
+}
+
+/// World space corners, bottom left and top right, of what the camera sees
+pub fn visible_area(camera_transform: &Transform, projection: &PixelProjection) -> (Vec2, Vec2) {
+    let center = camera_transform.translation.truncate();
+    return (
+        center + Vec2::new(projection.left, projection.bottom),
+        center + Vec2::new(projection.right, projection.top),
+    );
+}
+
+pub struct OffscreenPlugin;
+
+impl Plugin for OffscreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::InGame)
+                .with_system(despawn_offscreen)
+                .into(),
+        );
+    }
+}
+
+fn despawn_offscreen(
+    mut commands: Commands,
+    offscreen_query: Query<(
+        Entity,
+        &GlobalTransform,
+        &DespawnOffscreen,
+        Option<&Enemy>,
+        Option<&Pooled>,
+    )>,
+    camera_query: Query<(&Transform, &PixelProjection)>,
+    mut escaped_events: EventWriter<EnemyEscaped>,
+    mut pools: ResMut<EntityPools>,
+) {
+    let (camera_transform, projection) = match camera_query.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let (min, max) = visible_area(camera_transform, projection);
+
+    for (entity, transform, offscreen, enemy, pooled) in offscreen_query.iter() {
+        let position = transform.translation().truncate();
+        let margin = Vec2::splat(offscreen.margin);
+        if position.cmplt(min - margin).any() || position.cmpgt(max + margin).any() {
+            despawn_or_release(&mut commands, &mut pools, entity, pooled);
+            if enemy.is_some() {
+                escaped_events.send(EnemyEscaped { entity });
+            }
+        }
+    }
+}
@@ -10,11 +10,27 @@ use crate::{
     collision::Collider,
     components::Health,
     input_actions::InputAction,
-    shoot::Shootable,
-    utils::load_config::ConfigData,
-    CoreAssets, GameState,
+    pool::EntityPools,
+    shoot::{fire_weapon, Shootable},
+    utils::{config_watcher::ConfigChanged, load_config::ConfigData},
+    weapon::{WeaponResource, WeaponsAsset},
+    CoreAssets, GameState, UIWeaponLevel, UIWeaponResource,
 };
 
+/// Child of the player holding the `Shootable` of the secondary weapon slot
+#[derive(Component)]
+pub struct SecondaryWeapon;
+
+/// How long the shoot action has been held, for weapons with a charged shot
+#[derive(Component, Default)]
+pub struct Charge {
+    pub held: Stopwatch,
+}
+
+/// Child of the player showing the charge stage
+#[derive(Component)]
+pub struct ChargeIndicator;
+
 #[derive(Component)]
 pub struct Player {
     pub movement_speed: f32,
@@ -33,6 +49,10 @@ impl Plugin for PlayerPlugin {
                 .run_in_state(GameState::InGame)
                 .with_system(movement)
                 .with_system(shooting_system)
+                .with_system(apply_config_changes)
+                .with_system(update_weapon_level_ui)
+                .with_system(update_charge_indicator)
+                .with_system(update_weapon_resource_ui)
                 .into(),
         );
     }
@@ -113,12 +133,186 @@ fn movement(
     // }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn shooting_system(
-    mut shooter_query: Query<&mut Shootable, (With<Shootable>, With<Player>)>,
+    mut commands: Commands,
+    mut shooter_query: Query<
+        (&GlobalTransform, &mut Shootable, &mut Charge),
+        (With<Player>, Without<SecondaryWeapon>),
+    >,
+    mut secondary_query: Query<&mut Shootable, (With<SecondaryWeapon>, Without<Player>)>,
     action_query: Query<&ActionState<InputAction>, With<Player>>,
+    mut pools: ResMut<EntityPools>,
+    core_asssets: Res<CoreAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
+    time: Res<Time>,
 ) {
     let action_state = action_query.single();
-    let mut shootable = shooter_query.single_mut();
+    let (transform, mut shootable, mut charge) = shooter_query.single_mut();
 
-    shootable.is_shooting = action_state.pressed(InputAction::Shoot);
+    let weapons = weapons_assets.get(&core_asssets.weapons);
+    let charge_definition = weapons
+        .and_then(|weapons| weapons.weapons.get(&shootable.weapon))
+        .and_then(|weapon| weapon.charge.as_ref());
+
+    match charge_definition {
+        Some(charge_definition) => {
+            if action_state.pressed(InputAction::Shoot) {
+                charge.held.tick(time.delta());
+            }
+            let charged = charge_definition.charge(charge.held.elapsed_secs());
+
+            // Taps auto fire as usual until the shot starts charging
+            shootable.is_shooting = action_state.pressed(InputAction::Shoot) && charged.is_none();
+
+            if action_state.just_released(InputAction::Shoot) {
+                charge.held.reset();
+
+                let charged_weapon =
+                    weapons.and_then(|weapons| weapons.weapons.get(&charge_definition.weapon));
+                if let (Some(charged), Some(charged_weapon)) = (charged, charged_weapon) {
+                    let mut charged_weapon = charged_weapon.clone();
+                    charged_weapon.damage = (charged_weapon.damage as f32
+                        * charge_definition.damage_multiplier(charged))
+                    .round() as i32;
+
+                    fire_weapon(
+                        &mut commands,
+                        &mut pools,
+                        &core_asssets,
+                        &charged_weapon,
+                        &charged_weapon.stats(1),
+                        transform.translation().truncate(),
+                        shootable.direction.truncate(),
+                        shootable.source,
+                        false,
+                    );
+                }
+            }
+        }
+        None => {
+            shootable.is_shooting = action_state.pressed(InputAction::Shoot);
+            charge.held.reset();
+        }
+    }
+
+    for mut secondary in secondary_query.iter_mut() {
+        secondary.is_shooting = action_state.pressed(InputAction::Shoot_Secondary);
+    }
+}
+
+// Player copies its speeds from the config at spawn, refresh them on reload
+fn apply_config_changes(
+    mut config_changed_events: EventReader<ConfigChanged>,
+    mut player_query: Query<(&mut Player, &mut Shootable, &mut Charge, &mut Collider)>,
+    mut secondary_query: Query<&mut Shootable, (With<SecondaryWeapon>, Without<Player>)>,
+    config: Res<ConfigData>,
+) {
+    if config_changed_events.iter().last().is_none() {
+        return;
+    }
+
+    for (mut player, mut shootable, mut charge, mut collider) in player_query.iter_mut() {
+        player.movement_speed = config.player.movement_speed;
+        player.max_speed = config.player.max_speed;
+        *collider = Collider::circle(config.player.hitbox_radius);
+        if shootable.weapon != config.player.weapon {
+            shootable.weapon = config.player.weapon.clone();
+            shootable.reset();
+            charge.held.reset();
+        }
+    }
+
+    for mut secondary in secondary_query.iter_mut() {
+        if secondary.weapon != config.player.secondary_weapon {
+            secondary.weapon = config.player.secondary_weapon.clone();
+            secondary.reset();
+        }
+    }
+}
+
+fn update_weapon_level_ui(
+    player_query: Query<&Shootable, With<Player>>,
+    mut text_query: Query<&mut Text, With<UIWeaponLevel>>,
+) {
+    let level = match player_query.get_single() {
+        Ok(shootable) => shootable.level,
+        Err(_) => return,
+    };
+
+    for mut text in text_query.iter_mut() {
+        let value = format!("LV {}", level);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn update_charge_indicator(
+    player_query: Query<(&Shootable, &Charge), With<Player>>,
+    mut indicator_query: Query<(&mut Visibility, &mut TextureAtlasSprite), With<ChargeIndicator>>,
+    core_asssets: Res<CoreAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
+) {
+    let (shootable, charge) = match player_query.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    // Frame of the current stage, none while not charging
+    let frame = weapons_assets
+        .get(&core_asssets.weapons)
+        .and_then(|weapons| weapons.weapons.get(&shootable.weapon))
+        .and_then(|weapon| weapon.charge.as_ref())
+        .and_then(|definition| {
+            definition
+                .charge(charge.held.elapsed_secs())
+                .map(|charged| definition.stage_frames[definition.stage(charged)])
+        });
+
+    for (mut visibility, mut sprite) in indicator_query.iter_mut() {
+        visibility.is_visible = frame.is_some();
+        if let Some(frame) = frame {
+            sprite.index = frame;
+        }
+    }
+}
+
+fn update_weapon_resource_ui(
+    player_query: Query<&Shootable, With<Player>>,
+    mut text_query: Query<&mut Text, With<UIWeaponResource>>,
+    core_asssets: Res<CoreAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
+) {
+    let shootable = match player_query.get_single() {
+        Ok(shootable) => shootable,
+        Err(_) => return,
+    };
+    let resource = weapons_assets
+        .get(&core_asssets.weapons)
+        .and_then(|weapons| weapons.weapons.get(&shootable.weapon))
+        .and_then(|weapon| weapon.resource);
+
+    let gauge = &shootable.gauge;
+    let value = match resource {
+        Some(WeaponResource::Ammo { .. }) if gauge.locked => "RELOAD".to_string(),
+        Some(WeaponResource::Ammo { capacity, .. }) => {
+            format!("AMMO {}/{}", capacity.saturating_sub(gauge.spent), capacity)
+        }
+        Some(WeaponResource::Heat { .. }) if gauge.locked => "OVERHEAT".to_string(),
+        Some(WeaponResource::Heat { .. }) => format!("HEAT {}%", (gauge.heat * 100.) as u32),
+        None => String::new(),
+    };
+    let color = if gauge.locked {
+        Color::RED
+    } else {
+        Color::WHITE
+    };
+
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+        text.sections[0].style.color = color;
+    }
 }
@@ -0,0 +1,163 @@
+use bevy::{prelude::*, utils::HashSet};
+
+/// Counters of one `EntityPool`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Entities spawned because the pool was empty
+    pub created: usize,
+    /// Acquires served by a pooled entity
+    pub reused: usize,
+    pub released: usize,
+    /// Entities waiting in the pool
+    pub free: usize,
+    /// Entities currently handed out
+    pub active: usize,
+}
+
+/// Hidden entities waiting to be reused instead of spawning new ones.
+/// Released entities become available after the next `recycle`, once the
+/// commands stripping them have been applied.
+#[derive(Default)]
+pub struct EntityPool {
+    free: Vec<Entity>,
+    released: Vec<Entity>,
+    /// Free and released entities, guards against releasing one twice
+    pooled: HashSet<Entity>,
+    created: usize,
+    reused: usize,
+    release_count: usize,
+}
+
+impl EntityPool {
+    /// A pooled entity, or a new empty one when the pool is empty. The caller
+    /// inserts the components, replacing those of the previous use.
+    pub fn acquire(&mut self, commands: &mut Commands) -> Entity {
+        return match self.free.pop() {
+            Some(entity) => {
+                self.pooled.remove(&entity);
+                self.reused += 1;
+                entity
+            }
+            None => {
+                self.created += 1;
+                commands.spawn().id()
+            }
+        };
+    }
+
+    /// Hides `entity` and removes the components of `B` from it. Releasing
+    /// an entity that is already in the pool does nothing.
+    pub fn release<B: Bundle>(&mut self, commands: &mut Commands, entity: Entity) {
+        if !self.pooled.insert(entity) {
+            return;
+        }
+
+        self.release_count += 1;
+        self.released.push(entity);
+        commands
+            .entity(entity)
+            .remove_bundle::<B>()
+            .insert(Visibility { is_visible: false });
+    }
+
+    /// Makes the entities released so far available to `acquire`
+    pub fn recycle(&mut self) {
+        self.free.append(&mut self.released);
+    }
+
+    /// Forgets every pooled entity, for when they have been despawned
+    pub fn clear(&mut self) {
+        self.free.clear();
+        self.released.clear();
+        self.pooled.clear();
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let free = self.free.len() + self.released.len();
+        return PoolStats {
+            created: self.created,
+            reused: self.reused,
+            released: self.release_count,
+            free,
+            active: self.created.saturating_sub(free),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+
+    #[derive(Component)]
+    struct Behaviour;
+
+    #[test]
+    fn counts_acquires_releases_and_reuse() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut pool = EntityPool::default();
+
+        let mut commands = Commands::new(&mut queue, &world);
+        let first = pool.acquire(&mut commands);
+        let second = pool.acquire(&mut commands);
+        commands.entity(first).insert(Behaviour);
+        pool.release::<Behaviour>(&mut commands, first);
+        // Twice is ignored
+        pool.release::<Behaviour>(&mut commands, first);
+        queue.apply(&mut world);
+
+        assert_ne!(first, second);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                created: 2,
+                reused: 0,
+                released: 1,
+                free: 1,
+                active: 1,
+            }
+        );
+        assert!(world.get::<Behaviour>(first).is_none());
+        assert!(!world.get::<Visibility>(first).unwrap().is_visible);
+
+        // Not reused before the release has been recycled
+        let mut commands = Commands::new(&mut queue, &world);
+        let third = pool.acquire(&mut commands);
+        assert_ne!(third, first);
+
+        pool.recycle();
+        let reused = pool.acquire(&mut commands);
+        queue.apply(&mut world);
+
+        assert_eq!(reused, first);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                created: 3,
+                reused: 1,
+                released: 1,
+                free: 0,
+                active: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn clear_forgets_free_entities() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut pool = EntityPool::default();
+
+        let mut commands = Commands::new(&mut queue, &world);
+        let entity = pool.acquire(&mut commands);
+        pool.release::<Behaviour>(&mut commands, entity);
+        pool.recycle();
+        pool.clear();
+
+        assert_eq!(pool.stats().free, 0);
+        assert_ne!(pool.acquire(&mut commands), entity);
+        assert_eq!(pool.stats().created, 2);
+    }
+}
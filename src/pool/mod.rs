@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+
+use crate::{
+    animation::FrameAnimation,
+    collision::{Collider, CollisionLayers},
+    components::Collectable,
+    homing::Homing,
+    moveable::Moveable,
+    offscreen::DespawnOffscreen,
+    projectile::{Piercing, Projectile},
+    UIPoolStats,
+};
+
+mod entity_pool;
+
+pub use entity_pool::{EntityPool, PoolStats};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolKind {
+    Projectile,
+    /// Explosions and other animations that play once
+    Effect,
+    Collectable,
+}
+
+/// Entity from a pool, goes back to it instead of being despawned
+#[derive(Component)]
+pub struct Pooled(pub PoolKind);
+
+/// Components removed from an entity going back to its pool, so no system
+/// picks it up while it waits. They are inserted again on reuse.
+type PooledBehaviour = (
+    Projectile,
+    Piercing,
+    Homing,
+    Collider,
+    CollisionLayers,
+    Moveable,
+    DespawnOffscreen,
+    FrameAnimation,
+    Collectable,
+);
+
+#[derive(Default)]
+pub struct EntityPools {
+    projectiles: EntityPool,
+    effects: EntityPool,
+    collectables: EntityPool,
+}
+
+impl EntityPools {
+    pub fn acquire(&mut self, commands: &mut Commands, kind: PoolKind) -> Entity {
+        let entity = self.pool_mut(kind).acquire(commands);
+        commands.entity(entity).insert(Pooled(kind));
+        return entity;
+    }
+
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity, kind: PoolKind) {
+        self.pool_mut(kind)
+            .release::<PooledBehaviour>(commands, entity);
+    }
+
+    pub fn stats(&self, kind: PoolKind) -> PoolStats {
+        return match kind {
+            PoolKind::Projectile => self.projectiles.stats(),
+            PoolKind::Effect => self.effects.stats(),
+            PoolKind::Collectable => self.collectables.stats(),
+        };
+    }
+
+    /// Forgets every pooled entity, call it when despawning everything
+    pub fn clear(&mut self) {
+        for kind in [
+            PoolKind::Projectile,
+            PoolKind::Effect,
+            PoolKind::Collectable,
+        ] {
+            self.pool_mut(kind).clear();
+        }
+    }
+
+    fn pool_mut(&mut self, kind: PoolKind) -> &mut EntityPool {
+        return match kind {
+            PoolKind::Projectile => &mut self.projectiles,
+            PoolKind::Effect => &mut self.effects,
+            PoolKind::Collectable => &mut self.collectables,
+        };
+    }
+}
+
+/// Returns `entity` to its pool when it came from one, despawns it otherwise
+pub fn despawn_or_release(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    entity: Entity,
+    pooled: Option<&Pooled>,
+) {
+    match pooled {
+        Some(&Pooled(kind)) => pools.release(commands, entity, kind),
+        None => commands.entity(entity).despawn_recursive(),
+    }
+}
+
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EntityPools>()
+            .add_system_to_stage(CoreStage::Last, recycle_pools);
+    }
+}
+
+// Runs after the commands of the frame, released entities are stripped by now
+fn recycle_pools(
+    mut pools: ResMut<EntityPools>,
+    mut text_query: Query<&mut Text, With<UIPoolStats>>,
+) {
+    pools.projectiles.recycle();
+    pools.effects.recycle();
+    pools.collectables.recycle();
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = [
+            ("projectiles", PoolKind::Projectile),
+            ("effects", PoolKind::Effect),
+            ("collectables", PoolKind::Collectable),
+        ]
+        .iter()
+        .map(|&(name, kind)| {
+            let stats = pools.stats(kind);
+            format!(
+                "{} {} active {} free, {} created {} reused {} released",
+                name, stats.active, stats.free, stats.created, stats.reused, stats.released
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    }
+}
@@ -3,4 +3,12 @@ use bevy::prelude::*;
 #[derive(Component)]
 pub struct Projectile {
     pub source: Entity,
+    pub damage: i32,
+}
+
+/// Projectile that passes through targets instead of being destroyed
+#[derive(Component, Default)]
+pub struct Piercing {
+    /// Targets already damaged, each is only hit once
+    pub hit: Vec<Entity>,
 }
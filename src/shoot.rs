@@ -1,19 +1,121 @@
 use crate::{
-    collision::Collider, entities::entity_loader::craete_entity_from_atlas, moveable::Moveable,
-    projectile::Projectile, CoreAssets, GameState,
+    animation::{insert_animated_bundle, AnimationSheet},
+    collision::{Collider, CollisionLayer, CollisionLayers, COLLIDER_SIZE},
+    enemy::Enemy,
+    entities::entity_loader::insert_atlas_bundle,
+    homing::Homing,
+    moveable::{Moveable, RotationMode},
+    offscreen::DespawnOffscreen,
+    pool::{EntityPools, PoolKind},
+    projectile::{Piercing, Projectile},
+    weapon::{ProjectileSheet, WeaponDefinition, WeaponResource, WeaponStats, WeaponsAsset},
+    CoreAssets, GameState,
 };
 use bevy::{prelude::*, time::Stopwatch};
 use iyes_loopless::prelude::ConditionSet;
 
 #[derive(Component)]
 pub struct Shootable {
+    /// Id of the weapon in the weapons file
+    pub weapon: String,
     pub direction: Vec3,
     pub source: Entity,
 
     pub is_shooting: bool,
-    pub shoot_speed_per_ms: u128,
+    /// Weapon level, starting at 1
+    pub level: usize,
     pub time: Stopwatch,
+    /// Ammo or heat of weapons with a resource
+    pub gauge: WeaponGauge,
+    /// Shots of the current burst still to fire
+    burst_remaining: usize,
+    burst_time: Stopwatch,
 }
+
+/// State of a weapon's `WeaponResource`
+#[derive(Default)]
+pub struct WeaponGauge {
+    /// Shots fired since the last reload
+    pub spent: u32,
+    /// From 0 to 1, overheated at 1
+    pub heat: f32,
+    /// Reloading or overheated
+    pub locked: bool,
+    reload_time: Stopwatch,
+}
+
+impl WeaponGauge {
+    /// Uses up one shot, or `amount` of heat-per-shot for beams
+    pub fn spend(&mut self, resource: &WeaponResource, amount: f32) {
+        match *resource {
+            WeaponResource::Ammo { capacity, .. } => {
+                self.spent += 1;
+                if self.spent >= capacity {
+                    self.locked = true;
+                    self.reload_time.reset();
+                }
+            }
+            WeaponResource::Heat { heat_per_shot, .. } => {
+                self.heat = (self.heat + heat_per_shot * amount).min(1.);
+                if self.heat >= 1. {
+                    self.locked = true;
+                }
+            }
+        }
+    }
+
+    /// Reloads and cools down
+    pub fn tick(&mut self, resource: &WeaponResource, delta: std::time::Duration) {
+        match *resource {
+            WeaponResource::Ammo { reload_time, .. } => {
+                if self.locked {
+                    self.reload_time.tick(delta);
+                    if self.reload_time.elapsed_secs() >= reload_time {
+                        self.spent = 0;
+                        self.locked = false;
+                    }
+                }
+            }
+            WeaponResource::Heat {
+                cooling_rate,
+                recover_below,
+                ..
+            } => {
+                self.heat = (self.heat - cooling_rate * delta.as_secs_f32()).max(0.);
+                if self.locked && self.heat <= recover_below {
+                    self.locked = false;
+                }
+            }
+        }
+    }
+}
+
+impl Shootable {
+    pub fn new(weapon: impl Into<String>, direction: Vec3, source: Entity) -> Self {
+        Self {
+            weapon: weapon.into(),
+            direction,
+            source,
+            is_shooting: false,
+            level: 1,
+            time: Stopwatch::new(),
+            gauge: WeaponGauge::default(),
+            burst_remaining: 0,
+            burst_time: Stopwatch::new(),
+        }
+    }
+
+    /// Drops the rest of the current burst, restarts the fire interval and
+    /// refills the resource
+    pub fn reset(&mut self) {
+        self.time.reset();
+        self.gauge = WeaponGauge::default();
+        self.burst_remaining = 0;
+    }
+}
+
+const PROJECTILE_OFFSCREEN_MARGIN: f32 = 10.;
+
 pub struct ShootPlugin;
 
 impl Plugin for ShootPlugin {
@@ -29,43 +131,206 @@ impl Plugin for ShootPlugin {
 
 fn shooting_system(
     mut commands: Commands,
-    mut shooter_query: Query<(&mut Transform, &mut Shootable), With<Shootable>>,
+    mut shooter_query: Query<(&GlobalTransform, &mut Shootable, Option<&Enemy>)>,
+    mut pools: ResMut<EntityPools>,
     core_asssets: Res<CoreAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
     time: Res<Time>,
 ) {
-    for (shooter_transform, mut shootable) in shooter_query.iter_mut() {
-        if shootable.is_shooting {
-            shootable.time.tick(time.delta());
+    let weapons = match weapons_assets.get(&core_asssets.weapons) {
+        Some(weapons) => weapons,
+        None => return,
+    };
+
+    for (shooter_transform, mut shootable, enemy) in shooter_query.iter_mut() {
+        // Unknown ids are reported once by `check_weapon_ids`
+        let weapon = match weapons.weapons.get(&shootable.weapon) {
+            Some(weapon) => weapon,
+            None => {
+                shootable.is_shooting = false;
+                shootable.burst_remaining = 0;
+                continue;
+            }
+        };
+
+        // Reloads and cools down whether firing or not
+        if let Some(resource) = &weapon.resource {
+            shootable.gauge.tick(resource, time.delta());
+        }
+
+        if !shootable.is_shooting && shootable.burst_remaining == 0 {
+            continue;
+        }
+
+        // Beams are fired by the beam plugin
+        if weapon.beam.is_some() {
+            continue;
+        }
 
-            let pressed_millis = shootable.time.elapsed().as_millis();
+        let stats = weapon.stats(shootable.level);
 
-            if pressed_millis > shootable.shoot_speed_per_ms {
+        // A started burst is finished even when the trigger is let go
+        let fire = if shootable.gauge.locked {
+            // A reload or overheat also ends the burst
+            shootable.burst_remaining = 0;
+            false
+        } else if shootable.burst_remaining > 0 {
+            shootable.burst_time.tick(time.delta());
+            shootable.burst_time.elapsed().as_millis() >= weapon.burst_interval_ms as u128
+        } else {
+            shootable.time.tick(time.delta());
+            if shootable.time.elapsed().as_millis() > stats.interval_ms as u128 {
                 shootable.time.reset();
+                shootable.burst_remaining = weapon.burst_size;
+                true
+            } else {
+                false
+            }
+        };
 
-                let projectile = craete_entity_from_atlas(
-                    &mut commands,
-                    &core_asssets.general,
-                    0,
-                    Vec3::new(
-                        shooter_transform.translation.x,
-                        shooter_transform.translation.y,
-                        100.,
-                    ),
-                );
-
-                commands
-                    .entity(projectile)
-                    .insert(Name::new(format!("Projectile_{}", projectile.id())))
-                    .insert(Projectile {
-                        source: shootable.source,
-                    })
-                    .insert(Collider)
-                    .insert(Moveable {
-                        direction: shootable.direction,
-                        speed: 450.,
-                        auto_destroy: false,
-                    });
+        if fire {
+            shootable.burst_remaining -= 1;
+            shootable.burst_time.reset();
+            if let Some(resource) = &weapon.resource {
+                shootable.gauge.spend(resource, 1.);
             }
+
+            fire_weapon(
+                &mut commands,
+                &mut pools,
+                &core_asssets,
+                weapon,
+                &stats,
+                shooter_transform.translation().truncate(),
+                shootable.direction.truncate(),
+                shootable.source,
+                enemy.is_some(),
+            );
+        }
+    }
+}
+
+/// Fires one shot of `weapon` from every muzzle
+#[allow(clippy::too_many_arguments)]
+pub fn fire_weapon(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    core_asssets: &Res<CoreAssets>,
+    weapon: &WeaponDefinition,
+    stats: &WeaponStats,
+    position: Vec2,
+    direction: Vec2,
+    source: Entity,
+    hostile: bool,
+) {
+    let direction = direction.try_normalize().unwrap_or(Vec2::Y);
+
+    for muzzle in weapon.muzzle_positions(direction) {
+        for shot_direction in weapon.shot_directions(direction, stats) {
+            spawn_projectile(
+                commands,
+                pools,
+                core_asssets,
+                weapon,
+                position + muzzle,
+                shot_direction,
+                source,
+                hostile,
+            );
         }
     }
 }
+
+/// Movement of a projectile of `weapon` fired towards `direction`
+fn projectile_moveable(weapon: &WeaponDefinition, direction: Vec2) -> Moveable {
+    let mut moveable = Moveable::new(direction.extend(0.), weapon.speed)
+        .with_acceleration(weapon.acceleration)
+        .with_drag(weapon.drag)
+        .with_angular_velocity(weapon.angular_velocity);
+    if let Some(max_speed) = weapon.max_speed {
+        moveable = moveable.with_max_speed(max_speed);
+    }
+    if let Some(oscillation) = weapon.oscillation {
+        moveable = moveable.with_oscillation(oscillation);
+    }
+    if weapon.spin != 0. {
+        moveable = moveable.with_rotation_mode(RotationMode::Spin(weapon.spin));
+    }
+    return moveable;
+}
+
+/// Spawns one projectile of `weapon` from the pool, `hostile` ones hurt the
+/// player
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    pools: &mut EntityPools,
+    core_asssets: &Res<CoreAssets>,
+    weapon: &WeaponDefinition,
+    position: Vec2,
+    direction: Vec2,
+    source: Entity,
+    hostile: bool,
+) -> Entity {
+    let translation = position.extend(100.);
+    let sheet = match weapon.sprite.sheet {
+        ProjectileSheet::General => &core_asssets.general,
+        ProjectileSheet::Missile => &core_asssets.missile,
+    };
+    let projectile = pools.acquire(commands, PoolKind::Projectile);
+    if weapon.sprite.frames.len() > 1 {
+        insert_animated_bundle(
+            commands,
+            projectile,
+            translation,
+            &AnimationSheet {
+                handle: sheet.clone(),
+                frames: weapon.sprite.frames.clone(),
+            },
+            weapon.sprite.frame_duration,
+            true,
+            Color::WHITE,
+        );
+    } else {
+        insert_atlas_bundle(
+            commands,
+            projectile,
+            sheet,
+            weapon.sprite.frames[0],
+            translation,
+        );
+    }
+
+    commands
+        .entity(projectile)
+        .insert(Name::new(format!("Projectile_{}", projectile.id())))
+        .insert(Projectile {
+            source,
+            damage: weapon.damage,
+        })
+        .insert(Transform {
+            translation,
+            scale: Vec3::new(weapon.sprite.scale, weapon.sprite.scale, 1.),
+            ..default()
+        })
+        .insert(Collider::aabb(COLLIDER_SIZE * weapon.sprite.scale))
+        .insert(CollisionLayers::new(if hostile {
+            CollisionLayer::EnemyBullet
+        } else {
+            CollisionLayer::PlayerBullet
+        }))
+        .insert(projectile_moveable(weapon, direction))
+        .insert(DespawnOffscreen::new(PROJECTILE_OFFSCREEN_MARGIN));
+
+    if weapon.piercing {
+        commands.entity(projectile).insert(Piercing::default());
+    }
+    if let Some(homing) = weapon.homing {
+        commands.entity(projectile).insert(Homing {
+            turn_rate: homing.turn_rate,
+            target: None,
+        });
+    }
+
+    return projectile;
+}
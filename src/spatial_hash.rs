@@ -0,0 +1,181 @@
+use bevy::{prelude::*, utils::HashMap};
+
+/// Uniform grid of axis aligned boxes, for finding what overlaps an area
+/// without testing everything
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    entries: Vec<SpatialEntry>,
+}
+
+struct SpatialEntry {
+    entity: Entity,
+    min: Vec2,
+    max: Vec2,
+}
+
+impl SpatialHash {
+    /// `cell_size` should be about the size of the common boxes
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::default(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, center: Vec2, half_size: Vec2) {
+        let index = self.entries.len();
+        let entry = SpatialEntry {
+            entity,
+            min: center - half_size,
+            max: center + half_size,
+        };
+
+        let (min_cell, max_cell) = (self.cell(entry.min), self.cell(entry.max));
+        for x in min_cell.0..=max_cell.0 {
+            for y in min_cell.1..=max_cell.1 {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    /// Entities whose box overlaps the box around `center`
+    pub fn query_aabb(&self, center: Vec2, half_size: Vec2) -> Vec<Entity> {
+        let (min, max) = (center - half_size, center + half_size);
+        return self.query(min, max, |entry| {
+            entry.min.cmple(max).all() && entry.max.cmpge(min).all()
+        });
+    }
+
+    /// Entities whose box overlaps the circle
+    pub fn query_circle(&self, center: Vec2, radius: f32) -> Vec<Entity> {
+        let half_size = Vec2::new(radius, radius);
+        return self.query(center - half_size, center + half_size, |entry| {
+            let closest = center.clamp(entry.min, entry.max);
+            closest.distance_squared(center) <= radius * radius
+        });
+    }
+
+    fn query(&self, min: Vec2, max: Vec2, overlaps: impl Fn(&SpatialEntry) -> bool) -> Vec<Entity> {
+        let (min_cell, max_cell) = (self.cell(min), self.cell(max));
+
+        // Boxes spanning several cells are found more than once
+        let mut indices = Vec::new();
+        for x in min_cell.0..=max_cell.0 {
+            for y in min_cell.1..=max_cell.1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    indices.extend_from_slice(cell);
+                }
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+
+        return indices
+            .into_iter()
+            .map(|index| &self.entries[index])
+            .filter(|entry| overlaps(entry))
+            .map(|entry| entry.entity)
+            .collect();
+    }
+
+    fn cell(&self, position: Vec2) -> (i32, i32) {
+        let cell = (position / self.cell_size).floor();
+        return (cell.x as i32, cell.y as i32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+        entities.sort();
+        return entities;
+    }
+
+    #[test]
+    fn box_spanning_several_cells_is_found_once() {
+        let mut grid = SpatialHash::new(10.);
+        let wide = Entity::from_raw(1);
+        // Covers cells 0 to 4 on both axes
+        grid.insert(wide, Vec2::splat(25.), Vec2::splat(20.));
+
+        assert_eq!(
+            grid.query_aabb(Vec2::splat(25.), Vec2::splat(25.)),
+            vec![wide]
+        );
+        assert_eq!(grid.query_circle(Vec2::splat(25.), 30.), vec![wide]);
+    }
+
+    #[test]
+    fn only_overlapping_boxes_are_returned() {
+        let mut grid = SpatialHash::new(10.);
+        let near = Entity::from_raw(1);
+        let same_cell = Entity::from_raw(2);
+        let far = Entity::from_raw(3);
+        grid.insert(near, Vec2::new(2., 2.), Vec2::splat(1.));
+        grid.insert(same_cell, Vec2::new(8., 8.), Vec2::splat(1.));
+        grid.insert(far, Vec2::new(50., 50.), Vec2::splat(1.));
+
+        assert_eq!(
+            grid.query_aabb(Vec2::new(3., 3.), Vec2::splat(1.)),
+            vec![near]
+        );
+        assert_eq!(
+            sorted(grid.query_aabb(Vec2::new(5., 5.), Vec2::splat(5.))),
+            vec![near, same_cell]
+        );
+    }
+
+    #[test]
+    fn circle_skips_boxes_only_in_its_bounds() {
+        let mut grid = SpatialHash::new(10.);
+        let corner = Entity::from_raw(1);
+        let side = Entity::from_raw(2);
+        grid.insert(corner, Vec2::new(9., 9.), Vec2::splat(1.));
+        grid.insert(side, Vec2::new(9., 0.), Vec2::splat(1.));
+
+        // Both are in the box around the circle, the corner one is over 11 away
+        assert_eq!(grid.query_circle(Vec2::ZERO, 10.), vec![side]);
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        let mut grid = SpatialHash::new(10.);
+        let left = Entity::from_raw(1);
+        let right = Entity::from_raw(2);
+        // Cells -1 and 0 meet at the origin
+        grid.insert(left, Vec2::new(-3., -3.), Vec2::splat(2.));
+        grid.insert(right, Vec2::new(3., 3.), Vec2::splat(2.));
+
+        assert_eq!(
+            grid.query_aabb(Vec2::new(-2., -2.), Vec2::splat(1.)),
+            vec![left]
+        );
+        assert_eq!(grid.query_circle(Vec2::new(-6., -6.), 2.), vec![left]);
+        assert_eq!(
+            sorted(grid.query_aabb(Vec2::ZERO, Vec2::splat(2.))),
+            vec![left, right]
+        );
+        assert!(grid
+            .query_aabb(Vec2::new(-50., -50.), Vec2::splat(5.))
+            .is_empty());
+    }
+
+    #[test]
+    fn clear_empties_the_grid() {
+        let mut grid = SpatialHash::new(10.);
+        grid.insert(Entity::from_raw(1), Vec2::ZERO, Vec2::splat(5.));
+        grid.clear();
+
+        assert!(grid.query_aabb(Vec2::ZERO, Vec2::splat(5.)).is_empty());
+    }
+}
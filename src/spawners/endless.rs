@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    enemy::EnemyType,
+    enemy_fire::{BulletPattern, PatternStep},
+    movement::{path_movement::PathEnd, spline::PathKind},
+};
+
+use super::{formation::Formation, level_asset::WaveDefinition, wave_trigger::WaveTrigger};
+
+/// Weapon from the weapons file generated waves shoot with
+const ENDLESS_WEAPON: &str = "enemy_bullet";
+
+/// How hard the generated waves are at a given point of the run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    pub enemy_count: usize,
+    pub speed_multiplier: f32,
+    pub health_multiplier: f32,
+    pub bullet_density: f32,
+    /// Chance of a wave being made of heavy enemies
+    pub heavy_chance: f32,
+}
+
+/// Difficulty curve of the endless mode. Grows by one level per minute and
+/// per 10 000 points, every value is capped so late runs stay playable.
+pub fn difficulty(elapsed_seconds: f32, score: i64) -> Difficulty {
+    let level = elapsed_seconds.max(0.) / 60. + score.max(0) as f32 / 10_000.;
+
+    Difficulty {
+        enemy_count: (3 + (level * 2.) as usize).min(20),
+        speed_multiplier: (1. + level * 0.25).min(3.),
+        health_multiplier: (1. + level * 0.5).min(10.),
+        bullet_density: (0.5 + level * 0.5).min(4.),
+        heavy_chance: (0.1 + level * 0.1).min(0.6),
+    }
+}
+
+/// Seeded endless wave generator, the same seed always gives the same run
+/// for the same difficulty inputs
+pub struct EndlessMode {
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl EndlessMode {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Builds the next wave. `view_center` and `view_half_size` describe the
+    /// visible area, paths enter from above it and leave below it.
+    pub fn next_wave(
+        &mut self,
+        difficulty: &Difficulty,
+        view_center: Vec2,
+        view_half_size: Vec2,
+    ) -> WaveDefinition {
+        let rng = &mut self.rng;
+
+        let top = view_center.y + view_half_size.y + 20.;
+        let bottom = view_center.y - view_half_size.y - 20.;
+        let random_x =
+            |rng: &mut StdRng| view_center.x + rng.gen_range(-view_half_size.x..=view_half_size.x);
+
+        let path = vec![
+            [random_x(rng), top],
+            [
+                random_x(rng),
+                view_center.y + rng.gen_range(-0.5..=0.5) * view_half_size.y,
+            ],
+            [random_x(rng), bottom],
+        ];
+
+        let formation = match rng.gen_range(0..4) {
+            0 => Some(Formation::Line { spacing: 14. }),
+            1 => Some(Formation::V { spacing: 12. }),
+            2 => Some(Formation::Column { spacing: 14. }),
+            _ => None,
+        };
+        // Formations come in together, loose waves trickle in
+        let spawn_interval = match formation {
+            Some(_) => 0.,
+            None => rng.gen_range(0.3..=0.6),
+        };
+
+        let enemy_type = if rng.gen_bool(difficulty.heavy_chance as f64) {
+            EnemyType::Heavy
+        } else {
+            EnemyType::Plane
+        };
+
+        WaveDefinition {
+            trigger: WaveTrigger::EnemiesRemaining { count: 2 },
+            start_delay: rng.gen_range(0.5..=1.5),
+            enemy_count: difficulty.enemy_count,
+            spawn_interval,
+            formation,
+            rotate_formation: true,
+            speed: difficulty.speed_multiplier,
+            health_multiplier: difficulty.health_multiplier,
+            bullet_density: difficulty.bullet_density,
+            enemy_type,
+            path,
+            path_kind: PathKind::CatmullRom,
+            easing: Vec::new(),
+            path_end: PathEnd::Despawn,
+            steering: None,
+            fire: vec![PatternStep {
+                weapon: ENDLESS_WEAPON.to_string(),
+                pattern: BulletPattern::Aimed {
+                    count: 1,
+                    spread: 0.,
+                },
+                interval_ms: 2000,
+                duration: 1.,
+                rotation_speed: 0.,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEW_CENTER: Vec2 = Vec2::new(0., 100.);
+    const VIEW_HALF_SIZE: Vec2 = Vec2::new(120., 90.);
+
+    #[test]
+    fn difficulty_never_decreases() {
+        let mut previous = difficulty(0., 0);
+        for step in 1..200 {
+            let current = difficulty(step as f32 * 15., step * 500);
+            assert!(current.enemy_count >= previous.enemy_count);
+            assert!(current.speed_multiplier >= previous.speed_multiplier);
+            assert!(current.health_multiplier >= previous.health_multiplier);
+            assert!(current.bullet_density >= previous.bullet_density);
+            assert!(current.heavy_chance >= previous.heavy_chance);
+            previous = current;
+        }
+    }
+
+    #[test]
+    fn difficulty_is_capped() {
+        assert_eq!(
+            difficulty(1_000_000., 1_000_000_000),
+            Difficulty {
+                enemy_count: 20,
+                speed_multiplier: 3.,
+                health_multiplier: 10.,
+                bullet_density: 4.,
+                heavy_chance: 0.6,
+            }
+        );
+    }
+
+    #[test]
+    fn difficulty_ignores_negative_inputs() {
+        assert_eq!(difficulty(-10., -500), difficulty(0., 0));
+    }
+
+    #[test]
+    fn same_seed_gives_same_waves() {
+        let mut first = EndlessMode::new(42);
+        let mut second = EndlessMode::new(42);
+
+        for step in 0..20 {
+            let difficulty = difficulty(step as f32 * 30., step * 1000);
+            let a = first.next_wave(&difficulty, VIEW_CENTER, VIEW_HALF_SIZE);
+            let b = second.next_wave(&difficulty, VIEW_CENTER, VIEW_HALF_SIZE);
+
+            assert_eq!(a.path, b.path);
+            assert_eq!(a.formation, b.formation);
+            assert_eq!(a.enemy_type, b.enemy_type);
+            assert_eq!(a.enemy_count, b.enemy_count);
+            assert_eq!(a.start_delay, b.start_delay);
+            assert_eq!(a.spawn_interval, b.spawn_interval);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_waves() {
+        let difficulty = difficulty(0., 0);
+        let paths = |seed| {
+            let mut endless = EndlessMode::new(seed);
+            return (0..10)
+                .map(|_| {
+                    endless
+                        .next_wave(&difficulty, VIEW_CENTER, VIEW_HALF_SIZE)
+                        .path
+                })
+                .collect::<Vec<_>>();
+        };
+
+        assert_ne!(paths(1), paths(2));
+    }
+}
@@ -1,112 +1,333 @@
-use std::time::Duration;
-
-use bevy::prelude::*;
+use bevy::{prelude::*, time::Stopwatch};
 use bevy_pixel_camera::PixelProjection;
 use iyes_loopless::{
-    prelude::{ConditionSet, FixedTimestepStage},
+    prelude::{AppLooplessStateExt, ConditionSet},
     state::NextState,
 };
 
 use crate::{
-    enemy::spawn_enemy, movement::path_movement::PathMoveable, utils::load_config::ConfigData,
-    CoreAssets, GameState,
+    boss::BossEncounter,
+    campaign::StageAssets,
+    enemy::{spawn_enemy, EnemyType},
+    enemy_fire::{BulletEmitter, PatternStep},
+    movement::{
+        path_movement::{PathEnd, PathMoveable},
+        spline::SplinePath,
+        steering::{Steering, SteeringDefinition},
+    },
+    utils::load_config::ConfigData,
+    CoreAssets, GameMode, GameState, Score,
+};
+
+use super::{
+    endless::{difficulty, EndlessMode},
+    level_asset::{LevelAsset, LevelAssetLoader, WaveDefinition},
+    wave_trigger::{ScriptFlags, TriggerContext, WaveTrigger},
 };
 
 pub struct Wave {
+    trigger: WaveTrigger,
+    is_triggered: bool,
+    start_delay: Timer,
     enemies_to_spawn: usize,
     enemies_spawned: usize,
-    wave_position_y: f32,
-    pub enemy_move_positions: Vec<Vec2>,
+    /// Time since the start delay ran out
+    spawn_clock: Stopwatch,
+    spawn_interval: f32,
+    speed_multiplier: f32,
+    health_multiplier: f32,
+    enemy_type: EnemyType,
+    member_offsets: Vec<Vec2>,
+    rotate_formation: bool,
+    pub path: SplinePath,
+    path_end: PathEnd,
+    steering: Option<SteeringDefinition>,
+    fire: Vec<PatternStep>,
+    bullet_density: f32,
+}
+
+impl Wave {
+    fn is_spawned(&self) -> bool {
+        return self.enemies_spawned >= self.enemies_to_spawn;
+    }
+
+    /// How many enemies should be out by now, the first one comes right away
+    fn enemies_due(&self) -> usize {
+        if self.spawn_interval <= 0. {
+            return self.enemies_to_spawn;
+        }
+
+        let due = (self.spawn_clock.elapsed_secs() / self.spawn_interval) as usize + 1;
+        return due.min(self.enemies_to_spawn);
+    }
+}
+
+impl From<&WaveDefinition> for Wave {
+    fn from(definition: &WaveDefinition) -> Self {
+        Self {
+            trigger: definition.trigger.clone(),
+            is_triggered: false,
+            start_delay: Timer::from_seconds(definition.start_delay, false),
+            enemies_to_spawn: definition.enemy_count,
+            enemies_spawned: 0,
+            spawn_clock: Stopwatch::new(),
+            spawn_interval: definition.spawn_interval,
+            speed_multiplier: definition.speed,
+            health_multiplier: definition.health_multiplier,
+            enemy_type: definition.enemy_type,
+            member_offsets: definition.member_offsets(),
+            rotate_formation: definition.rotate_formation,
+            path: definition.spline_path(),
+            path_end: definition.path_end,
+            steering: definition.steering.clone(),
+            fire: definition.fire.clone(),
+            bullet_density: definition.bullet_density,
+        }
+    }
 }
 
 pub struct WaveData {
     pub waves: Vec<Wave>,
+    /// Index of `waves[0]`, cleared waves are dropped from the front
+    first_index: usize,
+    /// Time since the stage started, used by `WaveTrigger::Time`
+    elapsed: Stopwatch,
 }
 
 impl WaveData {
     fn has_waves(&self) -> bool {
-        // TODO This can be optimized?
-        return self
-            .waves
-            .iter()
-            .filter(|&wave| wave.enemies_spawned < wave.enemies_to_spawn)
-            .collect::<Vec<&Wave>>()
-            .len()
-            > 0;
+        return self.waves.iter().any(|wave| !wave.is_spawned());
     }
 }
 
+/// Which wave an enemy was spawned by, used to count what is left of a wave.
+/// Counts every wave of the stage, including the ones dropped from `WaveData`.
+#[derive(Component)]
+pub struct WaveMember {
+    pub wave_index: usize,
+}
+
 pub struct EnemyWaveSpawnerPlugin;
 
 impl Plugin for EnemyWaveSpawnerPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        let mut spawn_update = SystemStage::parallel();
-        spawn_update.add_system_set(
-            ConditionSet::new()
-                .run_in_state(GameState::InGame)
-                .with_system(spawn_wave)
-                .into(),
-        );
-
-        app.add_stage_before(
-            CoreStage::PreUpdate,
-            "EnemySpawnPreUpdate",
-            FixedTimestepStage::from_stage(Duration::from_millis(500), spawn_update),
-        )
-        // TODO theese should be loaded from file
-        .insert_resource(WaveData {
-            waves: vec![
-                Wave {
-                    enemies_spawned: 0,
-                    enemies_to_spawn: 5,
-                    wave_position_y: 0.,
-                    enemy_move_positions: vec![Vec2::new(300., 200.), Vec2::new(-230., -60.)],
-                },
-                Wave {
-                    enemies_spawned: 0,
-                    enemies_to_spawn: 5,
-                    wave_position_y: 15.,
-                    enemy_move_positions: vec![Vec2::new(300., 400.), Vec2::new(-430., -120.)],
-                },
-            ],
+        app.add_asset::<LevelAsset>()
+            .init_asset_loader::<LevelAssetLoader>()
+            .add_enter_system(GameState::InGame, setup_waves)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::InGame)
+                    .run_if_resource_exists::<WaveData>()
+                    .with_system(spawn_wave)
+                    .into(),
+            );
+    }
+}
+
+fn setup_waves(
+    mut commands: Commands,
+    stage_assets: Res<StageAssets>,
+    levels: Res<Assets<LevelAsset>>,
+    game_mode: Res<GameMode>,
+    config: Res<ConfigData>,
+) {
+    commands.insert_resource(ScriptFlags::default());
+
+    if *game_mode == GameMode::Endless {
+        let seed = match config.endless.seed {
+            0 => rand::random(),
+            seed => seed,
+        };
+        // Printed so that an interesting run can be replayed with --set endless.seed=...
+        println!("starting endless mode with seed {}", seed);
+
+        commands.insert_resource(WaveData {
+            waves: Vec::new(),
+            first_index: 0,
+            elapsed: Stopwatch::new(),
         });
+        commands.insert_resource(EndlessMode::new(seed));
+        commands.remove_resource::<BossEncounter>();
+        return;
+    }
+    commands.remove_resource::<EndlessMode>();
+
+    // The stage's level is loaded in LoadingStage, before InGame
+    let level = match levels.get(&stage_assets.level) {
+        Some(level) => level,
+        None => {
+            eprintln!("level of stage `{}` is not loaded", stage_assets.name);
+            commands.remove_resource::<WaveData>();
+            commands.remove_resource::<BossEncounter>();
+            commands.insert_resource(NextState(GameState::GameOver));
+            return;
+        }
+    };
+
+    commands.insert_resource(WaveData {
+        waves: level.waves.iter().map(Wave::from).collect(),
+        first_index: 0,
+        elapsed: Stopwatch::new(),
+    });
+
+    match &level.boss {
+        Some(boss) => commands.insert_resource(BossEncounter::new(boss.clone())),
+        None => commands.remove_resource::<BossEncounter>(),
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn spawn_wave(
     mut commands: Commands,
     camera_query: Query<&Transform, With<PixelProjection>>,
-    path_moveables_query: Query<&PathMoveable>,
+    wave_members_query: Query<&WaveMember>,
     core_asssets: Res<CoreAssets>,
     mut wave_data: ResMut<WaveData>,
+    boss_encounter: Option<ResMut<BossEncounter>>,
+    endless_mode: Option<ResMut<EndlessMode>>,
+    flags: Res<ScriptFlags>,
     config: Res<ConfigData>,
+    score: Res<Score>,
+    time: Res<Time>,
 ) {
-    // Check if it's time to spawn current wave
-    // We do this by looking at the cameras Y translation
-    if wave_data.has_waves() {
-        let camera_transform = camera_query.single();
-
-        for wave in wave_data.waves.iter_mut() {
-            if camera_transform.translation.y >= wave.wave_position_y {
-                if wave.enemies_spawned < wave.enemies_to_spawn {
-                    spawn_enemy(
-                        &mut commands,
-                        &core_asssets,
-                        // TODO might not need to clone here, use reference?
-                        wave.enemy_move_positions.clone(),
-                        config.enemies.movement_speed,
-                    );
-                    wave.enemies_spawned += 1;
+    wave_data.elapsed.tick(time.delta());
+    let camera_transform = camera_query.single();
+    let elapsed_seconds = wave_data.elapsed.elapsed_secs();
+
+    // Endless mode queues the next wave as soon as the last one is out
+    if let Some(mut endless_mode) = endless_mode {
+        if !wave_data.has_waves() {
+            let view_half_size = Vec2::new(
+                config.general.screen_height * config.general.base_aspect_ratio,
+                config.general.screen_height,
+            ) / 2.;
+            let wave = endless_mode.next_wave(
+                &difficulty(elapsed_seconds, score.amount),
+                camera_transform.translation.truncate(),
+                view_half_size,
+            );
+            wave_data.waves.push(Wave::from(&wave));
+        }
+    }
 
+    if !wave_data.has_waves() {
+        if wave_members_query.is_empty() {
+            // Boss comes in once the waves are cleared
+            if let Some(mut boss_encounter) = boss_encounter {
+                if !boss_encounter.is_defeated() {
+                    boss_encounter.start();
                     return;
                 }
             }
-        }
-    } else {
-        if path_moveables_query.iter().len() <= 0 {
+
             println!("level completed");
 
-            commands.insert_resource(NextState(GameState::GameOver));
+            commands.insert_resource(NextState(GameState::StageClear));
+        }
+        return;
+    }
+
+    let mut alive_per_wave = vec![0; wave_data.waves.len()];
+    for member in wave_members_query.iter() {
+        let alive = member
+            .wave_index
+            .checked_sub(wave_data.first_index)
+            .and_then(|index| alive_per_wave.get_mut(index));
+        if let Some(alive) = alive {
+            *alive += 1;
+        }
+    }
+
+    // Spawned and cleared waves are dropped so endless runs don't keep every
+    // wave they generated. The wave after them sees no previous wave, which
+    // triggers the same as a cleared one.
+    let cleared = wave_data
+        .waves
+        .iter()
+        .zip(alive_per_wave.iter())
+        .take_while(|(wave, &alive)| wave.is_spawned() && alive == 0)
+        .count();
+    wave_data.waves.drain(..cleared);
+    alive_per_wave.drain(..cleared);
+    wave_data.first_index += cleared;
+
+    let first_index = wave_data.first_index;
+    let waves = &mut wave_data.waves;
+    for index in 0..waves.len() {
+        let previous_wave_remaining = match index.checked_sub(1) {
+            Some(previous) if waves[previous].is_spawned() => Some(alive_per_wave[previous]),
+            Some(_) => None,
+            None => Some(0),
+        };
+
+        let wave = &mut waves[index];
+        if wave.is_spawned() {
+            continue;
+        }
+
+        if !wave.is_triggered {
+            wave.is_triggered = wave.trigger.is_met(&TriggerContext {
+                camera_y: camera_transform.translation.y,
+                elapsed_seconds,
+                previous_wave_remaining,
+                flags: &flags,
+            });
+            if !wave.is_triggered {
+                continue;
+            }
+        }
+
+        wave.start_delay.tick(time.delta());
+        if !wave.start_delay.finished() {
+            continue;
+        }
+
+        wave.spawn_clock.tick(time.delta());
+
+        while wave.enemies_spawned < wave.enemies_due() {
+            let path_moveable = PathMoveable::new(
+                wave.path.clone(),
+                config.enemies.movement_speed,
+                wave.speed_multiplier,
+            )
+            .with_offset(
+                wave.member_offsets[wave.enemies_spawned],
+                wave.rotate_formation,
+            )
+            .with_end(wave.path_end);
+
+            let enemy = spawn_enemy(
+                &mut commands,
+                &core_asssets,
+                path_moveable.current_position(),
+                wave.enemy_type,
+                wave.health_multiplier,
+            );
+            commands.entity(enemy).insert(WaveMember {
+                wave_index: first_index + index,
+            });
+            if !wave.fire.is_empty() {
+                commands
+                    .entity(enemy)
+                    .insert(BulletEmitter::new(wave.fire.clone(), wave.bullet_density));
+            }
+
+            // Steering waves only use the path to place and aim the enemies
+            match &wave.steering {
+                Some(steering) => {
+                    let (_, heading) = wave.path.sample(0.);
+                    // The steering lifetime decides when the enemy escapes
+                    commands.entity(enemy).insert(Steering::new(
+                        steering,
+                        wave.speed_multiplier,
+                        heading,
+                    ));
+                }
+                None => {
+                    commands.entity(enemy).insert(path_moveable);
+                }
+            }
+            wave.enemies_spawned += 1;
         }
     }
 }
@@ -0,0 +1,59 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Shape of a wave's squadron. Offsets are in formation space where +Y is the
+/// direction of travel and +X is to the right of it.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Formation {
+    /// Side by side, across the direction of travel
+    Line {
+        spacing: f32,
+    },
+    /// Leader in front, the rest trailing back on alternating sides
+    V {
+        spacing: f32,
+    },
+    /// One behind the other
+    Column {
+        spacing: f32,
+    },
+    Circle {
+        radius: f32,
+    },
+    /// Explicit `[x, y]` offsets, repeated if the wave has more enemies
+    Custom {
+        offsets: Vec<[f32; 2]>,
+    },
+}
+
+impl Formation {
+    /// Offset of every member of a `count` sized formation
+    pub fn offsets(&self, count: usize) -> Vec<Vec2> {
+        return (0..count).map(|index| self.offset(index, count)).collect();
+    }
+
+    fn offset(&self, index: usize, count: usize) -> Vec2 {
+        match self {
+            Formation::Line { spacing } => {
+                let center = (count as f32 - 1.) / 2.;
+                Vec2::new((index as f32 - center) * spacing, 0.)
+            }
+            Formation::V { spacing } => {
+                let rank = ((index + 1) / 2) as f32;
+                let side = if index % 2 == 1 { -1. } else { 1. };
+                Vec2::new(side * rank * spacing, -rank * spacing)
+            }
+            Formation::Column { spacing } => Vec2::new(0., -(index as f32) * spacing),
+            Formation::Circle { radius } => {
+                let angle = index as f32 / count as f32 * TAU;
+                Vec2::new(angle.cos(), angle.sin()) * *radius
+            }
+            Formation::Custom { offsets } => offsets
+                .get(index % offsets.len().max(1))
+                .map_or(Vec2::ZERO, |&offset| Vec2::from(offset)),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    boss::BossDefinition,
+    enemy::EnemyType,
+    enemy_fire::PatternStep,
+    movement::{
+        path_movement::PathEnd,
+        spline::{Easing, PathKind, SplinePath},
+        steering::SteeringDefinition,
+    },
+};
+
+use super::{formation::Formation, wave_trigger::WaveTrigger};
+
+/// Level file describing the enemy waves, loaded from `*.level.toml`
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "5b0e0c52-7d7e-4c8a-9d0b-3f7c2e4a1b6d"]
+pub struct LevelAsset {
+    pub waves: Vec<WaveDefinition>,
+    /// Fought once every wave has been cleared
+    #[serde(default)]
+    pub boss: Option<BossDefinition>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WaveDefinition {
+    pub trigger: WaveTrigger,
+    /// Seconds to wait after the trigger before the first enemy spawns
+    #[serde(default)]
+    pub start_delay: f32,
+    pub enemy_count: usize,
+    /// Seconds between two enemies of the wave, 0 spawns them all at once
+    pub spawn_interval: f32,
+    #[serde(default)]
+    pub formation: Option<Formation>,
+    /// Turn the formation with the path instead of keeping it axis aligned
+    #[serde(default = "default_rotate_formation")]
+    pub rotate_formation: bool,
+    /// Multiplier on `enemies.movement_speed` from the config
+    #[serde(default = "default_multiplier")]
+    pub speed: f32,
+    /// Multiplier on the enemy type's health
+    #[serde(default = "default_multiplier")]
+    pub health_multiplier: f32,
+    /// Multiplier on how often the wave's enemies shoot
+    #[serde(default = "default_multiplier")]
+    pub bullet_density: f32,
+    #[serde(default)]
+    pub enemy_type: EnemyType,
+    pub path: Vec<[f32; 2]>,
+    #[serde(default)]
+    pub path_kind: PathKind,
+    /// Easing of each path segment, missing ones are linear
+    #[serde(default)]
+    pub easing: Vec<Easing>,
+    #[serde(default)]
+    pub path_end: PathEnd,
+    /// Steer with these behaviours instead of following `path`, which then
+    /// only gives the spawn point and the initial heading
+    #[serde(default)]
+    pub steering: Option<SteeringDefinition>,
+    /// Pattern script every enemy of the wave fires, empty for no shooting
+    #[serde(default)]
+    pub fire: Vec<PatternStep>,
+}
+
+fn default_multiplier() -> f32 {
+    1.
+}
+
+fn default_rotate_formation() -> bool {
+    true
+}
+
+impl WaveDefinition {
+    pub fn path_points(&self) -> Vec<Vec2> {
+        return self.path.iter().map(|&point| Vec2::from(point)).collect();
+    }
+
+    pub fn spline_path(&self) -> SplinePath {
+        return SplinePath::new(self.path_kind, &self.path_points(), &self.easing);
+    }
+
+    pub fn member_offsets(&self) -> Vec<Vec2> {
+        return match &self.formation {
+            Some(formation) => formation.offsets(self.enemy_count),
+            None => vec![Vec2::ZERO; self.enemy_count],
+        };
+    }
+}
+
+#[derive(Default)]
+pub struct LevelAssetLoader;
+
+impl AssetLoader for LevelAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display().to_string();
+
+            let level: LevelAsset = toml::from_slice(bytes).map_err(|error| {
+                bevy::asset::Error::msg(format!("invalid level file `{}`: {}", path, error))
+            })?;
+            validate_level(&level).map_err(|error| {
+                bevy::asset::Error::msg(format!("invalid level file `{}`: {}", path, error))
+            })?;
+
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.toml"]
+    }
+}
+
+fn validate_level(level: &LevelAsset) -> Result<(), String> {
+    for (index, wave) in level.waves.iter().enumerate() {
+        if wave.path_kind.segment_count(wave.path.len()).is_none() {
+            let expected = match wave.path_kind {
+                PathKind::Bezier => "4, 7, 10... points",
+                _ => "at least two points",
+            };
+            return Err(format!("waves[{}].path needs {}", index, expected));
+        }
+        if wave.enemy_count == 0 {
+            return Err(format!(
+                "waves[{}].enemy_count must be greater than 0",
+                index
+            ));
+        }
+        if wave.spawn_interval.is_nan() || wave.spawn_interval < 0. {
+            return Err(format!(
+                "waves[{}].spawn_interval must be 0 or greater",
+                index
+            ));
+        }
+        if let Some(Formation::Custom { offsets }) = &wave.formation {
+            if offsets.is_empty() {
+                return Err(format!(
+                    "waves[{}].formation needs at least one offset",
+                    index
+                ));
+            }
+        }
+        if let Some(steering) = &wave.steering {
+            steering
+                .validate()
+                .map_err(|error| format!("waves[{}].{}", index, error))?;
+        }
+        for (step_index, step) in wave.fire.iter().enumerate() {
+            step.validate()
+                .map_err(|error| format!("waves[{}].fire[{}].{}", index, step_index, error))?;
+        }
+        if wave.start_delay.is_nan() || wave.start_delay < 0. {
+            return Err(format!("waves[{}].start_delay must be 0 or greater", index));
+        }
+        if wave.speed.is_nan() || wave.speed < 0. {
+            return Err(format!("waves[{}].speed must be 0 or greater", index));
+        }
+        if wave.health_multiplier.is_nan() || wave.health_multiplier <= 0. {
+            return Err(format!(
+                "waves[{}].health_multiplier must be greater than 0",
+                index
+            ));
+        }
+        if wave.bullet_density.is_nan() || wave.bullet_density < 0. {
+            return Err(format!(
+                "waves[{}].bullet_density must be 0 or greater",
+                index
+            ));
+        }
+    }
+
+    if let Some(boss) = &level.boss {
+        boss.validate()?;
+    }
+
+    return Ok(());
+}
@@ -1 +1,5 @@
+pub mod endless;
 pub mod enemy_wave_spawner;
+pub mod formation;
+pub mod level_asset;
+pub mod wave_trigger;
@@ -0,0 +1,59 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+/// When a wave starts spawning. Once met a trigger stays met, the wave's
+/// `start_delay` is counted from that moment.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WaveTrigger {
+    /// Camera has scrolled to `y`
+    Camera { y: f32 },
+    /// Seconds since the stage started
+    Time { seconds: f32 },
+    /// Previous wave has spawned everything and all of it is gone
+    PreviousCleared,
+    /// Previous wave has spawned everything and at most `count` are alive
+    EnemiesRemaining { count: usize },
+    /// Flag set from gameplay code through `ScriptFlags`
+    Flag { name: String },
+}
+
+/// State of the stage the triggers are checked against
+pub struct TriggerContext<'a> {
+    pub camera_y: f32,
+    pub elapsed_seconds: f32,
+    /// Alive enemies of the previous wave, `None` while it is still spawning
+    pub previous_wave_remaining: Option<usize>,
+    pub flags: &'a ScriptFlags,
+}
+
+impl WaveTrigger {
+    pub fn is_met(&self, context: &TriggerContext) -> bool {
+        match self {
+            WaveTrigger::Camera { y } => context.camera_y >= *y,
+            WaveTrigger::Time { seconds } => context.elapsed_seconds >= *seconds,
+            WaveTrigger::PreviousCleared => context.previous_wave_remaining == Some(0),
+            WaveTrigger::EnemiesRemaining { count } => context
+                .previous_wave_remaining
+                .map_or(false, |remaining| remaining <= *count),
+            WaveTrigger::Flag { name } => context.flags.is_set(name),
+        }
+    }
+}
+
+/// Named flags that gameplay code can raise to start scripted waves
+#[derive(Default)]
+pub struct ScriptFlags {
+    flags: HashSet<String>,
+}
+
+impl ScriptFlags {
+    pub fn set(&mut self, name: impl Into<String>) {
+        self.flags.insert(name.into());
+    }
+
+    pub fn is_set(&self, name: &str) -> bool {
+        return self.flags.contains(name);
+    }
+}
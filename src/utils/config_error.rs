@@ -0,0 +1,97 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Where a config value came from, used to point errors and warnings at the
+/// layer that needs fixing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigOrigin {
+    Defaults,
+    File {
+        path: PathBuf,
+        line: Option<usize>,
+    },
+    Env(String),
+    CommandLine,
+    /// All layers merged together, used when the failing key is unknown
+    Merged,
+}
+
+impl fmt::Display for ConfigOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigOrigin::Defaults => write!(f, "built-in defaults"),
+            ConfigOrigin::File {
+                path,
+                line: Some(line),
+            } => write!(f, "{}:{}", path.display(), line),
+            ConfigOrigin::File { path, line: None } => write!(f, "{}", path.display()),
+            ConfigOrigin::Env(name) => write!(f, "environment variable {}", name),
+            ConfigOrigin::CommandLine => write!(f, "command line"),
+            ConfigOrigin::Merged => write!(f, "merged config"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigErrorKind {
+    Io(io::Error),
+    Parse(String),
+    InvalidType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    InvalidValue(String),
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub origin: ConfigOrigin,
+    /// Dotted TOML key path, e.g. `player.max_speed`
+    pub key: Option<String>,
+    pub kind: ConfigErrorKind,
+}
+
+impl ConfigError {
+    pub fn file(&self) -> Option<&Path> {
+        match &self.origin {
+            ConfigOrigin::File { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match &self.origin {
+            ConfigOrigin::File { line, .. } => *line,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "config error in {}", self.origin)?;
+        if let Some(key) = &self.key {
+            write!(f, " at `{}`", key)?;
+        }
+
+        match &self.kind {
+            ConfigErrorKind::Io(error) => write!(f, ": could not read file, {}", error),
+            ConfigErrorKind::Parse(message) => write!(f, ": {}", message),
+            ConfigErrorKind::InvalidType { expected, found } => {
+                write!(f, ": expected {}, found {}", expected, found)
+            }
+            ConfigErrorKind::InvalidValue(message) => write!(f, ": {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ConfigErrorKind::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use bevy::prelude::*;
+
+use super::load_config::{config_files, load_config, ConfigData};
+
+/// Sent after `ConfigData` has been replaced with a freshly loaded config
+pub struct ConfigChanged;
+
+struct ConfigWatcher {
+    timer: Timer,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+pub struct ConfigWatcherPlugin;
+
+impl Plugin for ConfigWatcherPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ConfigWatcher {
+            timer: Timer::from_seconds(0.5, true),
+            files: watched_files(),
+        })
+        .add_event::<ConfigChanged>()
+        .add_system(reload_config);
+    }
+}
+
+fn watched_files() -> Vec<(PathBuf, Option<SystemTime>)> {
+    return config_files()
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            (path, modified)
+        })
+        .collect();
+}
+
+/// Polls the config files and reloads the whole layered config when any of
+/// them changes. A config that fails to load or validate is reported and the
+/// previous one is kept running.
+fn reload_config(
+    mut watcher: ResMut<ConfigWatcher>,
+    mut config: ResMut<ConfigData>,
+    mut config_changed_events: EventWriter<ConfigChanged>,
+    time: Res<Time>,
+) {
+    watcher.timer.tick(time.delta());
+    if !watcher.timer.just_finished() {
+        return;
+    }
+
+    let files = watched_files();
+    if files == watcher.files {
+        return;
+    }
+    watcher.files = files;
+
+    match load_config() {
+        Ok(new_config) => {
+            println!("config reloaded");
+            *config = new_config;
+            config_changed_events.send(ConfigChanged);
+        }
+        Err(error) => {
+            eprintln!("{}, keeping the previous config", error);
+        }
+    }
+}
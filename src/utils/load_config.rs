@@ -1,8 +1,19 @@
-use serde::Deserialize;
-use std::{fs, process::exit};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fs, io, mem,
+    path::{Path, PathBuf},
+};
+use toml::Value;
+
+use super::config_error::{ConfigError, ConfigErrorKind, ConfigOrigin};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+const ENV_PREFIX: &str = "PLANES_";
 
 // Data =========================================================
-#[derive(serde::Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct ConfigData {
     pub general: General,
     pub paths: Paths,
@@ -10,72 +21,749 @@ pub struct ConfigData {
     pub player: Player,
     pub animations: Animations,
     pub enemies: Enemies,
+    pub endless: Endless,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct General {
     pub base_aspect_ratio: f32,
     pub screen_height: f32,
     pub scroll_speed: f32,
 }
 
-#[derive(Deserialize)]
+impl Default for General {
+    fn default() -> Self {
+        Self {
+            base_aspect_ratio: 1.77,
+            screen_height: 130.,
+            scroll_speed: 5.,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Paths {
     pub tiles_path: String,
     pub planes_path: String,
     pub background_path: String,
 }
 
-#[derive(Deserialize)]
+impl Default for Paths {
+    fn default() -> Self {
+        Self {
+            tiles_path: "tiles.png".to_string(),
+            planes_path: "player.png".to_string(),
+            background_path: "background_0.png".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Sprites {
     pub sprite_scale: f32,
     pub tile_size: f32,
     pub tile_padding: f32,
 }
 
-#[derive(Deserialize)]
+impl Default for Sprites {
+    fn default() -> Self {
+        Self {
+            sprite_scale: 1.,
+            tile_size: 16.,
+            tile_padding: 1.,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Player {
     pub base_health: i32,
     pub movement_speed: f32,
     pub max_speed: f32,
+    /// Weapon id from the weapons file
+    pub weapon: String,
+    /// Weapon id of the secondary weapon slot
+    pub secondary_weapon: String,
+    /// Radius of the hitbox enemy fire has to hit, smaller than the ship
+    pub hitbox_radius: f32,
+    /// Collectables this close to the ship are picked up
+    pub pickup_radius: f32,
+    /// Dying above weapon level 1 costs a level and refills the health
+    /// instead of ending the game
+    pub weapon_levels_as_lives: bool,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            base_health: 100,
+            movement_speed: 1700.,
+            max_speed: 500.,
+            weapon: "cannon".to_string(),
+            secondary_weapon: "missile".to_string(),
+            hitbox_radius: 3.,
+            pickup_radius: 14.,
+            weapon_levels_as_lives: false,
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Animations {
     pub default_frame_duration: f32,
-    pub explosion_frame_duratioon: f32,
+    pub explosion_frame_duration: f32,
 }
 
-#[derive(Deserialize)]
+impl Default for Animations {
+    fn default() -> Self {
+        Self {
+            default_frame_duration: 0.2,
+            explosion_frame_duration: 0.1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Enemies {
+    /// Units per second along the path
     pub movement_speed: f32,
 }
 
+impl Default for Enemies {
+    fn default() -> Self {
+        Self {
+            movement_speed: 60.,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Endless {
+    /// Seed of the endless mode wave generator, 0 picks a random seed
+    pub seed: u64,
+}
+
 // =============================================================
 
-pub fn load_config() -> ConfigData {
-    // TODO Replace with relative path
-    let filename = "/Users/mikkoryynanen/Desktop/Projects/Rust/planes/config.toml";
+// Command line ================================================
+/// Config related flags read from the command line.
+/// `--config <path>` adds one more file on top of the other layers and
+/// `--set section.key=value` overrides a single value.
+#[derive(Default)]
+struct ConfigArgs {
+    config_path: Option<PathBuf>,
+    overrides: Vec<String>,
+    print_config: bool,
+}
+
+impl ConfigArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut config_args = ConfigArgs::default();
+
+        while let Some(arg) = args.next() {
+            if arg == "--config" {
+                config_args.config_path = args.next().map(PathBuf::from);
+            } else if let Some(path) = arg.strip_prefix("--config=") {
+                config_args.config_path = Some(PathBuf::from(path));
+            } else if arg == "--set" {
+                if let Some(value) = args.next() {
+                    config_args.overrides.push(value);
+                }
+            } else if let Some(value) = arg.strip_prefix("--set=") {
+                config_args.overrides.push(value.to_string());
+            } else if arg == "--print-config" {
+                config_args.print_config = true;
+            }
+        }
+
+        return config_args;
+    }
+}
+// =============================================================
+
+/// Remembers which layer set each dotted key path last
+#[derive(Default)]
+struct ConfigSources {
+    origins: HashMap<String, ConfigOrigin>,
+}
+
+impl ConfigSources {
+    fn record(&mut self, key: String, origin: ConfigOrigin) {
+        self.origins.insert(key, origin);
+    }
+
+    fn origin(&self, key: &str) -> ConfigOrigin {
+        return self
+            .origins
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigOrigin::Defaults);
+    }
+
+    fn error(&self, key: &str, kind: ConfigErrorKind) -> ConfigError {
+        return ConfigError {
+            origin: self.origin(key),
+            key: Some(key.to_string()),
+            kind,
+        };
+    }
+}
+
+/// A config file layer, read from disk by `load_config`
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Config merged from every layer
+pub struct LayeredConfig {
+    pub data: ConfigData,
+    /// Unknown keys, only warned about so that old config files keep working
+    pub warnings: Vec<String>,
+}
+
+/// Resolves the config by layering, from lowest to highest priority:
+/// built-in defaults, `config.toml` in the working directory or next to the
+/// executable, the user override file, `PLANES_*` environment variables and
+/// finally the `--config` / `--set` command line flags.
+pub fn load_config() -> Result<ConfigData, ConfigError> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config_args = ConfigArgs::parse(args.iter().cloned());
+
+    let files = file_paths(&config_args)
+        .iter()
+        .map(|path| read_file(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let env_vars: Vec<(String, String)> = env::vars().collect();
+
+    let config = layer_config(ConfigData::default(), &files, &env_vars, &args)?;
+    for warning in config.warnings.iter() {
+        eprintln!("warning: {}", warning);
+    }
+
+    if config_args.print_config {
+        println!("{:#?}", config.data);
+    }
+
+    return Ok(config.data);
+}
+
+/// Layering of `load_config` without touching the file system or process.
+/// `files` are the config files in layer order, the one named by `--config`
+/// in `args` goes above the environment variables in `env_vars`.
+pub fn layer_config(
+    defaults: ConfigData,
+    files: &[ConfigFile],
+    env_vars: &[(String, String)],
+    args: &[String],
+) -> Result<LayeredConfig, ConfigError> {
+    let args = ConfigArgs::parse(args.iter().cloned());
+    let mut sources = ConfigSources::default();
+    let is_args_file = |file: &&ConfigFile| args.config_path.as_ref() == Some(&file.path);
+
+    let defaults = Value::try_from(defaults).map_err(|error| ConfigError {
+        origin: ConfigOrigin::Defaults,
+        key: None,
+        kind: ConfigErrorKind::Parse(error.to_string()),
+    })?;
+    let mut merged = defaults.clone();
+
+    for file in files.iter().filter(|file| !is_args_file(file)) {
+        merge_file(&mut merged, &mut sources, file)?;
+    }
+
+    for (name, value) in env_vars.iter() {
+        if let Some(key) = name.strip_prefix(ENV_PREFIX) {
+            // PLANES_PLAYER_MOVEMENT_SPEED -> player.movement_speed
+            if let Some((section, field)) = key.to_lowercase().split_once('_') {
+                set_value(&mut merged, &[section, field], parse_value(value));
+                sources.record(
+                    format!("{}.{}", section, field),
+                    ConfigOrigin::Env(name.clone()),
+                );
+            }
+        }
+    }
+
+    if let Some(path) = &args.config_path {
+        let file = files.iter().find(is_args_file).ok_or_else(|| ConfigError {
+            origin: ConfigOrigin::File {
+                path: path.clone(),
+                line: None,
+            },
+            key: None,
+            kind: ConfigErrorKind::Io(io::Error::new(io::ErrorKind::NotFound, "file was not read")),
+        })?;
+        merge_file(&mut merged, &mut sources, file)?;
+    }
+
+    for assignment in args.overrides.iter() {
+        let (key, value) = assignment.split_once('=').ok_or_else(|| ConfigError {
+            origin: ConfigOrigin::CommandLine,
+            key: None,
+            kind: ConfigErrorKind::Parse(format!(
+                "expected --set section.key=value, found `{}`",
+                assignment
+            )),
+        })?;
+
+        let key = key.trim();
+        let key_path: Vec<&str> = key.split('.').collect();
+        set_value(&mut merged, &key_path, parse_value(value.trim()));
+        sources.record(key.to_string(), ConfigOrigin::CommandLine);
+    }
+
+    let mut warnings = Vec::new();
+    check_schema(&merged, &defaults, "", &sources, &mut warnings)?;
+
+    let data: ConfigData = merged.try_into().map_err(|error| ConfigError {
+        origin: ConfigOrigin::Merged,
+        key: None,
+        kind: ConfigErrorKind::Parse(error.to_string()),
+    })?;
+
+    validate(&data, &sources)?;
+
+    return Ok(LayeredConfig { data, warnings });
+}
+
+/// Every config file that `load_config` currently reads, in layer order
+pub fn config_files() -> Vec<PathBuf> {
+    return file_paths(&ConfigArgs::parse(env::args().skip(1)));
+}
+
+fn file_paths(args: &ConfigArgs) -> Vec<PathBuf> {
+    return base_config_path()
+        .into_iter()
+        .chain(user_config_path())
+        .chain(args.config_path.clone())
+        .collect();
+}
+
+fn base_config_path() -> Option<PathBuf> {
+    let working_dir = env::current_dir().ok();
+    let executable_dir = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+
+    return working_dir
+        .into_iter()
+        .chain(executable_dir)
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file());
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    let path = config_dir.join("planes").join(CONFIG_FILE_NAME);
+    return if path.is_file() { Some(path) } else { None };
+}
+
+fn read_file(path: &Path) -> Result<ConfigFile, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|error| ConfigError {
+        origin: ConfigOrigin::File {
+            path: path.to_path_buf(),
+            line: None,
+        },
+        key: None,
+        kind: ConfigErrorKind::Io(error),
+    })?;
+
+    return Ok(ConfigFile {
+        path: path.to_path_buf(),
+        contents,
+    });
+}
+
+fn merge_file(
+    merged: &mut Value,
+    sources: &mut ConfigSources,
+    file: &ConfigFile,
+) -> Result<(), ConfigError> {
+    let ConfigFile { path, contents } = file;
+
+    let layer: Value = toml::from_str(contents).map_err(|error| ConfigError {
+        origin: ConfigOrigin::File {
+            path: path.to_path_buf(),
+            line: error.line_col().map(|(line, _)| line + 1),
+        },
+        key: None,
+        kind: ConfigErrorKind::Parse(error.to_string()),
+    })?;
+
+    for (key, line) in key_lines(contents) {
+        sources.record(
+            key,
+            ConfigOrigin::File {
+                path: path.to_path_buf(),
+                line: Some(line),
+            },
+        );
+    }
+
+    merge_values(merged, layer);
+    return Ok(());
+}
+
+/// Finds the line of every `[section]` header and `key = value` pair so
+/// errors can point at them, toml does not keep spans for parsed values.
+fn key_lines(contents: &str) -> Vec<(String, usize)> {
+    let mut keys = Vec::new();
+    let mut section = String::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            section = line
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+            keys.push((section.clone(), index + 1));
+        } else if let Some((key, _)) = line.split_once('=') {
+            let key = key.trim().trim_matches('"');
+            if section.is_empty() {
+                keys.push((key.to_string(), index + 1));
+            } else {
+                keys.push((format!("{}.{}", section, key), index + 1));
+            }
+        }
+    }
+
+    return keys;
+}
+
+/// Merges `layer` on top of `base`, tables are merged key by key and any
+/// other value replaces the one below it.
+fn merge_values(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Table(base_table), Value::Table(layer_table)) => {
+            for (key, value) in layer_table {
+                match base_table.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base_table.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+fn set_value(root: &mut Value, key_path: &[&str], value: Value) {
+    let mut layer = value;
+    for key in key_path.iter().rev() {
+        let mut table = toml::value::Table::new();
+        table.insert(key.to_string(), layer);
+        layer = Value::Table(table);
+    }
+
+    merge_values(root, layer);
+}
+
+/// Values from the environment and command line are parsed as TOML so that
+/// numbers and booleans keep their type, anything else is kept as a string.
+fn parse_value(raw: &str) -> Value {
+    return toml::from_str::<Value>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|table| table.get("value").cloned())
+        .unwrap_or_else(|| Value::String(raw.to_string()));
+}
+
+// Validation ==================================================
+/// Compares the merged values against the defaults, which double as the
+/// schema. Unknown keys are only warned about so that old config files keep
+/// working, wrong types are errors.
+fn check_schema(
+    value: &Value,
+    schema: &Value,
+    prefix: &str,
+    sources: &ConfigSources,
+    warnings: &mut Vec<String>,
+) -> Result<(), ConfigError> {
+    if let (Value::Table(table), Value::Table(schema_table)) = (value, schema) {
+        for (key, child) in table.iter() {
+            let path = if prefix.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", prefix, key)
+            };
 
-    let contents = match fs::read_to_string(filename) {
-        Ok(c) => c,
-        Err(error) => {
-            eprintln!("Could not read file `{}`, error {}", filename, error);
-            exit(1);
+            match schema_table.get(key) {
+                Some(expected) => {
+                    if !is_same_type(expected, child) {
+                        return Err(sources.error(
+                            &path,
+                            ConfigErrorKind::InvalidType {
+                                expected: expected.type_str(),
+                                found: child.type_str(),
+                            },
+                        ));
+                    }
+                    check_schema(child, expected, &path, sources, warnings)?;
+                }
+                None => {
+                    warnings.push(format!(
+                        "unknown config key `{}` in {}",
+                        path,
+                        sources.origin(&path)
+                    ));
+                }
+            }
         }
+    }
+
+    return Ok(());
+}
+
+fn is_same_type(expected: &Value, found: &Value) -> bool {
+    return match (expected, found) {
+        // `scroll_speed = 5` is fine for a float field
+        (Value::Float(_), Value::Integer(_)) => true,
+        _ => mem::discriminant(expected) == mem::discriminant(found),
     };
+}
 
-    let data: ConfigData = match toml::from_str(&contents) {
-        Ok(d) => d,
-        Err(error) => {
-            eprintln!(
-                "Unable to load data from `{}`, error {}",
-                filename,
-                error.to_owned()
-            );
-            exit(1);
+fn validate(data: &ConfigData, sources: &ConfigSources) -> Result<(), ConfigError> {
+    let check = |is_valid: bool, key: &str, message: &str| {
+        if is_valid {
+            Ok(())
+        } else {
+            Err(sources.error(key, ConfigErrorKind::InvalidValue(message.to_string())))
         }
     };
+    let positive = |value: f32| value.is_finite() && value > 0.;
+    let non_negative = |value: f32| value.is_finite() && value >= 0.;
+
+    let general = &data.general;
+    check(
+        positive(general.base_aspect_ratio),
+        "general.base_aspect_ratio",
+        "must be greater than 0",
+    )?;
+    check(
+        positive(general.screen_height),
+        "general.screen_height",
+        "must be greater than 0",
+    )?;
+    check(
+        non_negative(general.scroll_speed),
+        "general.scroll_speed",
+        "must be 0 or greater",
+    )?;
+    check(
+        general.screen_height * general.base_aspect_ratio >= 1.,
+        "general.base_aspect_ratio",
+        "screen_height * base_aspect_ratio must be at least one pixel wide",
+    )?;
+
+    let paths = &data.paths;
+    check(
+        !paths.tiles_path.is_empty(),
+        "paths.tiles_path",
+        "must not be empty",
+    )?;
+    check(
+        !paths.planes_path.is_empty(),
+        "paths.planes_path",
+        "must not be empty",
+    )?;
+    check(
+        !paths.background_path.is_empty(),
+        "paths.background_path",
+        "must not be empty",
+    )?;
+
+    let sprites = &data.sprites;
+    check(
+        positive(sprites.sprite_scale),
+        "sprites.sprite_scale",
+        "must be greater than 0",
+    )?;
+    check(
+        positive(sprites.tile_size),
+        "sprites.tile_size",
+        "must be greater than 0",
+    )?;
+    check(
+        non_negative(sprites.tile_padding),
+        "sprites.tile_padding",
+        "must be 0 or greater",
+    )?;
+    check(
+        sprites.tile_padding < sprites.tile_size,
+        "sprites.tile_padding",
+        "must be smaller than sprites.tile_size",
+    )?;
+
+    let player = &data.player;
+    check(
+        player.base_health > 0,
+        "player.base_health",
+        "must be greater than 0",
+    )?;
+    check(
+        non_negative(player.movement_speed),
+        "player.movement_speed",
+        "must be 0 or greater",
+    )?;
+    check(
+        non_negative(player.max_speed),
+        "player.max_speed",
+        "must be 0 or greater",
+    )?;
+    check(
+        !player.weapon.is_empty(),
+        "player.weapon",
+        "must not be empty",
+    )?;
+    check(
+        !player.secondary_weapon.is_empty(),
+        "player.secondary_weapon",
+        "must not be empty",
+    )?;
+
+    let animations = &data.animations;
+    check(
+        positive(animations.default_frame_duration),
+        "animations.default_frame_duration",
+        "must be greater than 0",
+    )?;
+    check(
+        positive(animations.explosion_frame_duration),
+        "animations.explosion_frame_duration",
+        "must be greater than 0",
+    )?;
+
+    check(
+        non_negative(data.enemies.movement_speed),
+        "enemies.movement_speed",
+        "must be 0 or greater",
+    )?;
+
+    return Ok(());
+}
+// =============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(contents: &str) -> ConfigFile {
+        return ConfigFile {
+            path: PathBuf::from("config.toml"),
+            contents: contents.to_string(),
+        };
+    }
+
+    fn layer(files: &[ConfigFile]) -> Result<LayeredConfig, ConfigError> {
+        return layer_config(ConfigData::default(), files, &[], &[]);
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = layer(&[]).unwrap();
+        assert_eq!(config.data.general.screen_height, 130.);
+        assert!(config.warnings.is_empty());
+    }
+
+    #[test]
+    fn wrong_type_is_an_error() {
+        let error = layer(&[file("[general]\nscreen_height = \"tall\"\n")])
+            .err()
+            .unwrap();
+
+        assert_eq!(error.key.as_deref(), Some("general.screen_height"));
+        assert!(matches!(
+            error.kind,
+            ConfigErrorKind::InvalidType {
+                expected: "float",
+                found: "string",
+            }
+        ));
+    }
+
+    #[test]
+    fn negative_screen_height_is_an_error() {
+        let error = layer(&[file("[general]\nscreen_height = -10.0\n")])
+            .err()
+            .unwrap();
+
+        assert_eq!(error.key.as_deref(), Some("general.screen_height"));
+        assert!(matches!(error.kind, ConfigErrorKind::InvalidValue(_)));
+    }
+
+    #[test]
+    fn error_points_at_the_line() {
+        let contents = "# Planes config\n\n[general]\nscroll_speed = 5.0\nscreen_height = -10.0\n";
+        let error = layer(&[file(contents)]).err().unwrap();
+
+        assert_eq!(error.file(), Some(Path::new("config.toml")));
+        assert_eq!(error.line(), Some(5));
+    }
+
+    #[test]
+    fn parse_error_points_at_the_line() {
+        let error = layer(&[file("[general]\n\nscreen_height = = 1\n")])
+            .err()
+            .unwrap();
+
+        assert!(matches!(error.kind, ConfigErrorKind::Parse(_)));
+        assert_eq!(error.line(), Some(3));
+    }
+
+    #[test]
+    fn unknown_key_is_a_warning() {
+        let config = layer(&[file("[animations]\nexplosion_frame_duratioon = 0.1\n")]).unwrap();
+
+        assert_eq!(config.warnings.len(), 1);
+        assert!(config.warnings[0].contains("animations.explosion_frame_duratioon"));
+        assert!(config.warnings[0].contains("config.toml:2"));
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let env_vars = [("PLANES_GENERAL_SCROLL_SPEED".to_string(), "7.0".to_string())];
+        let args = ["--set".to_string(), "general.screen_height=200".to_string()];
+        let config = layer_config(
+            ConfigData::default(),
+            &[file(
+                "[general]\nscroll_speed = 2.0\nscreen_height = 150.0\n",
+            )],
+            &env_vars,
+            &args,
+        )
+        .unwrap();
+
+        assert_eq!(config.data.general.scroll_speed, 7.);
+        assert_eq!(config.data.general.screen_height, 200.);
+    }
+
+    #[test]
+    fn config_flag_file_goes_above_the_environment() {
+        let env_vars = [("PLANES_GENERAL_SCROLL_SPEED".to_string(), "7.0".to_string())];
+        let args = ["--config".to_string(), "override.toml".to_string()];
+        let files = [ConfigFile {
+            path: PathBuf::from("override.toml"),
+            contents: "[general]\nscroll_speed = 3.0\n".to_string(),
+        }];
+        let config = layer_config(ConfigData::default(), &files, &env_vars, &args).unwrap();
 
-    return data;
+        assert_eq!(config.data.general.scroll_speed, 3.);
+    }
 }
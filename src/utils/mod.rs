@@ -1 +1,3 @@
+pub mod config_error;
+pub mod config_watcher;
 pub mod load_config;
@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use iyes_loopless::prelude::AppLooplessStateExt;
+use serde::Deserialize;
+
+use crate::{
+    campaign::StageAssets, moveable::Oscillation, spawners::level_asset::LevelAsset,
+    utils::load_config::ConfigData, CoreAssets, GameState,
+};
+
+// Data =========================================================
+/// Every weapon in the game by id, loaded from `*.weapons.toml`
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "2f6c8e41-9b3d-4a57-8c1e-7d4b0a6f3e92"]
+pub struct WeaponsAsset {
+    pub weapons: HashMap<String, WeaponDefinition>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WeaponDefinition {
+    pub sprite: ProjectileSprite,
+    /// Units per second
+    pub speed: f32,
+    pub damage: i32,
+    /// Milliseconds between two bursts
+    pub interval_ms: u64,
+    /// Degrees between the outermost projectiles of one shot
+    #[serde(default)]
+    pub spread: f32,
+    /// Projectiles per shot, fanned out over `spread`
+    #[serde(default = "default_one")]
+    pub projectile_count: usize,
+    /// Shots per burst
+    #[serde(default = "default_one")]
+    pub burst_size: usize,
+    /// Milliseconds between the shots of a burst
+    #[serde(default)]
+    pub burst_interval_ms: u64,
+    /// Where the shots leave the shooter, +Y is the firing direction. Every
+    /// muzzle fires each shot.
+    #[serde(default = "default_muzzle_offsets")]
+    pub muzzle_offsets: Vec<[f32; 2]>,
+    /// Stats of level 2 onwards, the fields above are level 1
+    #[serde(default)]
+    pub upgrades: Vec<WeaponStats>,
+    /// Projectiles steer towards the nearest enemy
+    #[serde(default)]
+    pub homing: Option<HomingDefinition>,
+    /// Fire a continuous beam instead of projectiles, `sprite` is stretched
+    /// along it
+    #[serde(default)]
+    pub beam: Option<BeamDefinition>,
+    /// Projectiles pass through targets, damaging each of them once
+    #[serde(default)]
+    pub piercing: bool,
+    /// Units per second squared, negative slows the projectiles down
+    #[serde(default)]
+    pub acceleration: f32,
+    /// Fraction of the speed projectiles lose per second
+    #[serde(default)]
+    pub drag: f32,
+    /// Speed the projectiles can't go over, unlimited when missing
+    #[serde(default)]
+    pub max_speed: Option<f32>,
+    /// Radians per second the projectiles curve, positive is counterclockwise
+    #[serde(default)]
+    pub angular_velocity: f32,
+    /// Side to side motion of the projectiles
+    #[serde(default)]
+    pub oscillation: Option<Oscillation>,
+    /// Radians per second the projectile sprites spin, they face their
+    /// direction of travel when 0
+    #[serde(default)]
+    pub spin: f32,
+    /// Holding the trigger charges a shot of another weapon
+    #[serde(default)]
+    pub charge: Option<ChargeDefinition>,
+    /// Limits how long the weapon can fire, unlimited when missing
+    #[serde(default)]
+    pub resource: Option<WeaponResource>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WeaponResource {
+    /// `capacity` shots, refilled all at once `reload_time` seconds after the
+    /// last one
+    Ammo { capacity: u32, reload_time: f32 },
+    /// Every shot adds `heat_per_shot`, beams add it per second of firing.
+    /// Cools by `cooling_rate` per second. Reaching 1 overheats the weapon,
+    /// locking it until it has cooled down to `recover_below`.
+    Heat {
+        heat_per_shot: f32,
+        cooling_rate: f32,
+        #[serde(default)]
+        recover_below: f32,
+    },
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChargeDefinition {
+    /// Weapon id fired when the trigger is let go of after `threshold`
+    pub weapon: String,
+    /// Seconds the trigger has to be held, shorter taps fire normally
+    pub threshold: f32,
+    /// Seconds until the shot is fully charged
+    pub full_time: f32,
+    /// Damage multiplier of a full charge, grows from 1 at `threshold`
+    pub max_damage_multiplier: f32,
+    /// General sheet frames shown on the ship while charging, one per stage.
+    /// The last one shows once fully charged.
+    pub stage_frames: Vec<usize>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct BeamDefinition {
+    pub damage_per_second: f32,
+    /// Reach when nothing is hit
+    pub length: f32,
+    pub width: f32,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct HomingDefinition {
+    /// Radians per second
+    pub turn_rate: f32,
+}
+
+/// Part of a weapon that changes with its level
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WeaponStats {
+    pub interval_ms: u64,
+    pub spread: f32,
+    pub projectile_count: usize,
+}
+
+/// Frames from one of the projectile sheets, more than one frame loops them
+#[derive(Deserialize, Clone)]
+pub struct ProjectileSprite {
+    #[serde(default)]
+    pub sheet: ProjectileSheet,
+    pub frames: Vec<usize>,
+    #[serde(default = "default_frame_duration")]
+    pub frame_duration: f32,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectileSheet {
+    /// `CoreAssets.general`
+    #[default]
+    General,
+    /// `CoreAssets.missile`
+    Missile,
+}
+
+fn default_one() -> usize {
+    1
+}
+
+fn default_muzzle_offsets() -> Vec<[f32; 2]> {
+    vec![[0., 0.]]
+}
+
+fn default_frame_duration() -> f32 {
+    0.1
+}
+
+fn default_scale() -> f32 {
+    1.
+}
+
+impl ChargeDefinition {
+    /// How far the shot is charged after holding for `held` seconds, from 0
+    /// at `threshold` to 1. `None` before `threshold`.
+    pub fn charge(&self, held: f32) -> Option<f32> {
+        if held < self.threshold {
+            return None;
+        }
+        return Some(((held - self.threshold) / (self.full_time - self.threshold)).min(1.));
+    }
+
+    pub fn damage_multiplier(&self, charge: f32) -> f32 {
+        return 1. + (self.max_damage_multiplier - 1.) * charge;
+    }
+
+    /// Index into `stage_frames` for `charge`
+    pub fn stage(&self, charge: f32) -> usize {
+        let last = self.stage_frames.len().saturating_sub(1);
+        return ((charge * last as f32) as usize).min(last);
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.weapon.is_empty() {
+            return Err("charge.weapon must not be empty".to_string());
+        }
+        if self.threshold.is_nan() || self.threshold < 0. {
+            return Err("charge.threshold must be 0 or greater".to_string());
+        }
+        if self.full_time.is_nan() || self.full_time <= self.threshold {
+            return Err("charge.full_time must be greater than charge.threshold".to_string());
+        }
+        if self.max_damage_multiplier.is_nan() || self.max_damage_multiplier < 1. {
+            return Err("charge.max_damage_multiplier must be 1 or greater".to_string());
+        }
+        if self.stage_frames.is_empty() {
+            return Err("charge.stage_frames needs at least one frame".to_string());
+        }
+        return Ok(());
+    }
+}
+
+impl WeaponDefinition {
+    pub fn max_level(&self) -> usize {
+        return self.upgrades.len() + 1;
+    }
+
+    /// Stats at `level`, starting at 1 and capped to the highest level
+    pub fn stats(&self, level: usize) -> WeaponStats {
+        return match level.min(self.max_level()).checked_sub(2) {
+            Some(upgrade) => self.upgrades[upgrade],
+            None => WeaponStats {
+                interval_ms: self.interval_ms,
+                spread: self.spread,
+                projectile_count: self.projectile_count,
+            },
+        };
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.sprite.frames.is_empty() {
+            return Err("sprite.frames needs at least one frame".to_string());
+        }
+        if self.sprite.frame_duration.is_nan() || self.sprite.frame_duration <= 0. {
+            return Err("sprite.frame_duration must be greater than 0".to_string());
+        }
+        if self.sprite.scale.is_nan() || self.sprite.scale <= 0. {
+            return Err("sprite.scale must be greater than 0".to_string());
+        }
+        if self.speed.is_nan() || self.speed <= 0. {
+            return Err("speed must be greater than 0".to_string());
+        }
+        for level in 1..=self.max_level() {
+            let stats = self.stats(level);
+            let prefix = match level {
+                1 => String::new(),
+                _ => format!("upgrades[{}].", level - 2),
+            };
+            if stats.spread.is_nan() || !(0. ..=360.).contains(&stats.spread) {
+                return Err(format!("{}spread must be between 0 and 360", prefix));
+            }
+            if stats.projectile_count == 0 {
+                return Err(format!("{}projectile_count must be greater than 0", prefix));
+            }
+        }
+        if !self.acceleration.is_finite() {
+            return Err("acceleration must be a number".to_string());
+        }
+        if self.drag.is_nan() || self.drag < 0. {
+            return Err("drag must be 0 or greater".to_string());
+        }
+        if let Some(max_speed) = self.max_speed {
+            if max_speed.is_nan() || max_speed <= 0. {
+                return Err("max_speed must be greater than 0".to_string());
+            }
+        }
+        if !self.angular_velocity.is_finite() {
+            return Err("angular_velocity must be a number".to_string());
+        }
+        match self.oscillation {
+            Some(Oscillation::Sine {
+                amplitude,
+                frequency,
+            })
+            | Some(Oscillation::ZigZag {
+                amplitude,
+                frequency,
+            }) => {
+                if !amplitude.is_finite() {
+                    return Err("oscillation.amplitude must be a number".to_string());
+                }
+                if frequency.is_nan() || frequency <= 0. {
+                    return Err("oscillation.frequency must be greater than 0".to_string());
+                }
+            }
+            None => {}
+        }
+        if !self.spin.is_finite() {
+            return Err("spin must be a number".to_string());
+        }
+        if let Some(homing) = &self.homing {
+            if homing.turn_rate.is_nan() || homing.turn_rate < 0. {
+                return Err("homing.turn_rate must be 0 or greater".to_string());
+            }
+        }
+        if let Some(beam) = &self.beam {
+            if beam.damage_per_second.is_nan() || beam.damage_per_second < 0. {
+                return Err("beam.damage_per_second must be 0 or greater".to_string());
+            }
+            if beam.length.is_nan() || beam.length <= 0. {
+                return Err("beam.length must be greater than 0".to_string());
+            }
+            if beam.width.is_nan() || beam.width <= 0. {
+                return Err("beam.width must be greater than 0".to_string());
+            }
+        }
+        if let Some(charge) = &self.charge {
+            charge.validate()?;
+        }
+        match self.resource {
+            Some(WeaponResource::Ammo {
+                capacity,
+                reload_time,
+            }) => {
+                if self.beam.is_some() {
+                    return Err("resource ammo can't be used by beams, use heat".to_string());
+                }
+                if capacity == 0 {
+                    return Err("resource.capacity must be greater than 0".to_string());
+                }
+                if reload_time.is_nan() || reload_time < 0. {
+                    return Err("resource.reload_time must be 0 or greater".to_string());
+                }
+            }
+            Some(WeaponResource::Heat {
+                heat_per_shot,
+                cooling_rate,
+                recover_below,
+            }) => {
+                if heat_per_shot.is_nan() || heat_per_shot <= 0. {
+                    return Err("resource.heat_per_shot must be greater than 0".to_string());
+                }
+                if cooling_rate.is_nan() || cooling_rate <= 0. {
+                    return Err("resource.cooling_rate must be greater than 0".to_string());
+                }
+                if recover_below.is_nan() || !(0. ..1.).contains(&recover_below) {
+                    return Err("resource.recover_below must be between 0 and 1".to_string());
+                }
+            }
+            None => {}
+        }
+        if self.burst_size == 0 {
+            return Err("burst_size must be greater than 0".to_string());
+        }
+        if self.muzzle_offsets.is_empty() {
+            return Err("muzzle_offsets needs at least one offset".to_string());
+        }
+        return Ok(());
+    }
+
+    /// Directions of the projectiles of one shot fired towards `direction`
+    pub fn shot_directions(&self, direction: Vec2, stats: &WeaponStats) -> Vec<Vec2> {
+        if stats.projectile_count == 1 {
+            return vec![direction];
+        }
+
+        let spread = stats.spread.to_radians();
+        let step = spread / (stats.projectile_count - 1) as f32;
+        return (0..stats.projectile_count)
+            .map(|index| Mat2::from_angle(-spread / 2. + step * index as f32) * direction)
+            .collect();
+    }
+
+    /// Muzzle positions relative to the shooter when firing towards `direction`
+    pub fn muzzle_positions(&self, direction: Vec2) -> Vec<Vec2> {
+        let right = Vec2::new(direction.y, -direction.x);
+        return self
+            .muzzle_offsets
+            .iter()
+            .map(|&[x, y]| right * x + direction * y)
+            .collect();
+    }
+}
+
+#[derive(Default)]
+pub struct WeaponsAssetLoader;
+
+impl AssetLoader for WeaponsAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display().to_string();
+
+            let weapons: WeaponsAsset = toml::from_slice(bytes).map_err(|error| {
+                bevy::asset::Error::msg(format!("invalid weapons file `{}`: {}", path, error))
+            })?;
+            for (id, weapon) in weapons.weapons.iter() {
+                weapon.validate().map_err(|error| {
+                    bevy::asset::Error::msg(format!(
+                        "invalid weapons file `{}`: weapons.{}.{}",
+                        path, id, error
+                    ))
+                })?;
+            }
+            // Charged shots fire projectiles of another weapon
+            for (id, weapon) in weapons.weapons.iter() {
+                if let Some(charge) = &weapon.charge {
+                    let valid = weapons
+                        .weapons
+                        .get(&charge.weapon)
+                        .map_or(false, |charged| charged.beam.is_none());
+                    if !valid {
+                        return Err(bevy::asset::Error::msg(format!(
+                            "invalid weapons file `{}`: weapons.{}.charge.weapon `{}` is not a projectile weapon",
+                            path, id, charge.weapon
+                        )));
+                    }
+                }
+            }
+
+            load_context.set_default_asset(LoadedAsset::new(weapons));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["weapons.toml"]
+    }
+}
+// =============================================================
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WeaponsAsset>()
+            .init_asset_loader::<WeaponsAssetLoader>()
+            .add_enter_system(GameState::InGame, check_weapon_ids);
+    }
+}
+
+/// Reports weapon ids of the config and the stage's level that are missing
+/// from the weapons file. Checked once here, shooters skip unknown weapons.
+fn check_weapon_ids(
+    core_assets: Res<CoreAssets>,
+    stage_assets: Res<StageAssets>,
+    weapons_assets: Res<Assets<WeaponsAsset>>,
+    levels: Res<Assets<LevelAsset>>,
+    config: Res<ConfigData>,
+) {
+    let weapons = match weapons_assets.get(&core_assets.weapons) {
+        Some(weapons) => weapons,
+        None => return,
+    };
+
+    let mut used = vec![
+        ("player.weapon".to_string(), &config.player.weapon),
+        (
+            "player.secondary_weapon".to_string(),
+            &config.player.secondary_weapon,
+        ),
+    ];
+    if let Some(level) = levels.get(&stage_assets.level) {
+        for (wave_index, wave) in level.waves.iter().enumerate() {
+            for (step_index, step) in wave.fire.iter().enumerate() {
+                used.push((
+                    format!("waves[{}].fire[{}].weapon", wave_index, step_index),
+                    &step.weapon,
+                ));
+            }
+        }
+        if let Some(boss) = &level.boss {
+            for (phase_index, phase) in boss.phases.iter().enumerate() {
+                for (weapon_index, weapon) in phase.weapons.iter().enumerate() {
+                    used.push((
+                        format!(
+                            "boss.phases[{}].weapons[{}].weapon",
+                            phase_index, weapon_index
+                        ),
+                        &weapon.weapon,
+                    ));
+                }
+            }
+        }
+    }
+
+    for (key, id) in used {
+        if !weapons.weapons.contains_key(id) {
+            eprintln!("unknown weapon `{}` at `{}`", id, key);
+        }
+    }
+}